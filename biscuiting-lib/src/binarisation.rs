@@ -0,0 +1,144 @@
+use image::{GenericImageView, GrayImage, Luma, Rgba};
+
+/// Maps every pixel of an RGBA image, which unlike `imageproc::map::map_colors`
+/// may be a view into a larger buffer.
+pub fn map_pixels<I, F>(image: &I, f: F) -> GrayImage
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+    F: Fn(Rgba<u8>) -> Luma<u8>,
+{
+    let (width, height) = image.dimensions();
+    GrayImage::from_fn(width, height, |x, y| f(image.get_pixel(x, y)))
+}
+
+/// The level chosen by Otsu's method for a histogram of luminance levels, like
+/// `imageproc::contrast::otsu_level` but over whichever pixels were counted.
+/// Levels up to and including it fall on one side.
+pub fn otsu_level(histogram: &[u32; 256]) -> u8 {
+    let total_weight: u64 = histogram.iter().map(|count| *count as u64).sum();
+    let total_sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(level, count)| level as f64 * *count as f64)
+        .sum();
+    let mut background_weight = 0u64;
+    let mut background_sum = 0f64;
+    let mut largest_variance = 0f64;
+    let mut best_level = 0u8;
+    for (level, count) in histogram.iter().enumerate() {
+        background_weight += *count as u64;
+        if background_weight == 0 {
+            continue;
+        }
+        let foreground_weight = total_weight - background_weight;
+        if foreground_weight == 0 {
+            break;
+        }
+        background_sum += level as f64 * *count as f64;
+        let background_mean = background_sum / background_weight as f64;
+        let foreground_mean = (total_sum - background_sum) / foreground_weight as f64;
+        let variance = background_weight as f64
+            * foreground_weight as f64
+            * (background_mean - foreground_mean).powi(2);
+        if variance > largest_variance {
+            largest_variance = variance;
+            best_level = level as u8;
+        }
+    }
+    best_level
+}
+
+/// The largest difference between `a` and `b` in any one channel, alpha
+/// included.
+pub fn per_channel_distance(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).abs())
+        .max()
+        .unwrap_or(0) as f32
+}
+
+/// CIE L*a*b* coordinates of an sRGB colour with white at the D65 point. Alpha is
+/// ignored.
+pub fn to_lab(color: Rgba<u8>) -> [f32; 3] {
+    let linear = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(color[0]), linear(color[1]), linear(color[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// The CIE76 colour difference ΔE*ab between two L*a*b* colours, where about
+/// 2.3 is just noticeable.
+pub fn perceptual_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_channel_distance_is_largest_channel_difference() {
+        let white = Rgba([255u8; 4]);
+        assert_eq!(0.0, per_channel_distance(white, white));
+        assert_eq!(
+            12.0,
+            per_channel_distance(white, Rgba([250, 243, 255, 255]))
+        );
+        assert_eq!(255.0, per_channel_distance(white, Rgba([255, 255, 255, 0])));
+    }
+
+    #[test]
+    fn test_otsu_level_matches_imageproc() {
+        let image = GrayImage::from_fn(16, 16, |x, y| Luma([(x * 13 + y * y * 7) as u8]));
+        let mut histogram = [0u32; 256];
+        for level in image.pixels() {
+            histogram[level[0] as usize] += 1;
+        }
+
+        assert_eq!(
+            imageproc::contrast::otsu_level(&image),
+            otsu_level(&histogram)
+        );
+    }
+
+    #[test]
+    fn test_perceptual_distance_between_lab_colours() {
+        let white = to_lab(Rgba([255u8; 4]));
+        let black = to_lab(Rgba([0, 0, 0, 255]));
+        assert!((white[0] - 100.0).abs() < 0.01, "{:?}", white);
+        assert!(
+            white[1].abs() < 0.01 && white[2].abs() < 0.01,
+            "{:?}",
+            white
+        );
+        assert_eq!([0.0, 0.0, 0.0], black);
+        assert!((perceptual_distance(white, black) - 100.0).abs() < 0.01);
+
+        let near_white = to_lab(Rgba([250, 250, 250, 255]));
+        let pale_blue = to_lab(Rgba([240, 240, 255, 255]));
+        assert!(perceptual_distance(white, near_white) < 2.3);
+        assert!(perceptual_distance(white, pale_blue) > 2.3);
+    }
+}
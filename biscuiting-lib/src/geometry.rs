@@ -0,0 +1,239 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+/// Twice the signed area of a ring by the shoelace formula, positive when the
+/// ring runs counter-clockwise with `y` growing upwards, as in GeoJSON.
+pub fn signed_area_doubled(ring: &[(f32, f32)]) -> f32 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|((x0, y0), (x1, y1))| x0 * y1 - x1 * y0)
+        .sum()
+}
+
+/// Orients a ring counter-clockwise for an outer border or clockwise for a
+/// hole, in the sense of `signed_area_doubled`, and closes it by repeating the
+/// first point at the end. Rings with no area keep their order.
+pub fn normalise_ring(ring: &[(f32, f32)], is_hole: bool) -> Vec<(f32, f32)> {
+    let mut normalised = ring.to_vec();
+    let area = signed_area_doubled(ring);
+    if (is_hole && area > 0.0) || (!is_hole && area < 0.0) {
+        normalised[1..].reverse();
+    }
+    if let Some(first) = normalised.first().copied() {
+        normalised.push(first);
+    }
+    normalised
+}
+
+/// An identifier for whatever is centred at `(x, y)` that does not depend on
+/// the order things were found in. The point is snapped to a grid of cells
+/// `precision` wide and the cell is hashed, so the same point always gives the
+/// same identifier and nearby points within a cell share it.
+pub fn stable_id(x: f32, y: f32, precision: f32) -> u32 {
+    let cell_x = (x as f64 / precision as f64).round() as i64;
+    let cell_y = (y as f64 / precision as f64).round() as i64;
+    mix((cell_x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (cell_y as u64))
+}
+
+/// Makes every identifier in `ids` unique. Of those sharing an identifier, such
+/// as a courtyard block and the island in its middle, the one with the largest
+/// `sizes` entry keeps it, then the earliest. The rest are rehashed with their
+/// rank among them until the result is not otherwise used.
+pub fn settle_id_clashes(ids: &mut [u32], sizes: &[u32]) {
+    let mut used: HashSet<u32> = ids.iter().copied().collect();
+    if used.len() == ids.len() {
+        return;
+    }
+    let original = ids.to_vec();
+    let mut order: Vec<usize> = (0..ids.len()).collect();
+    order.sort_by_key(|index| (original[*index], Reverse(sizes[*index]), *index));
+    let mut rank = 0;
+    for (position, index) in order.iter().enumerate() {
+        let id = original[*index];
+        if position == 0 || original[order[position - 1]] != id {
+            rank = 0;
+            continue;
+        }
+        loop {
+            rank += 1;
+            let candidate = mix(((id as u64) << 32) | rank);
+            if used.insert(candidate) {
+                ids[*index] = candidate;
+                break;
+            }
+        }
+    }
+}
+
+fn mix(value: u64) -> u32 {
+    let mut hash = value;
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^= hash >> 31;
+    (hash ^ (hash >> 32)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_labelling::{find_contour_hierarchy_in_luma, Point, Tracing};
+    use image::Luma;
+    use imageproc::definitions::Image;
+    use imageproc::region_labelling::Connectivity;
+
+    fn examples() -> Vec<Image<Luma<u32>>> {
+        vec![
+            gray_image!(type: u32,
+                255, 0;
+                  0, 0),
+            gray_image!(type: u32,
+                0,   0,   0, 0;
+                0, 255, 255, 0;
+                0, 255, 255, 0;
+                0,   0,   0, 0),
+            gray_image!(type: u32,
+                0,   0,   0, 0;
+                0, 255, 255, 0;
+                0,   0, 255, 0;
+                0,   0,   0, 0),
+            gray_image!(type: u32,
+                0,   0,   0, 0;
+                0,   0, 255, 0;
+                0, 255, 255, 0;
+                0,   0,   0, 0),
+            gray_image!(type: u32,
+                1, 1, 1, 1, 1;
+                1, 0, 0, 0, 1;
+                1, 0, 2, 0, 1;
+                1, 0, 0, 0, 1;
+                1, 1, 1, 1, 1),
+            gray_image!(type: u32,
+                0, 0, 0, 0, 0, 0, 0;
+                0, 1, 1, 0, 2, 2, 0;
+                0, 1, 0, 0, 2, 0, 0;
+                0, 1, 1, 0, 2, 2, 2;
+                0, 0, 0, 0, 0, 0, 0),
+        ]
+    }
+
+    fn to_floats(ring: &[Point<u32>]) -> Vec<(f32, f32)> {
+        ring.iter()
+            .map(|point| (point.x as f32, point.y as f32))
+            .collect()
+    }
+
+    /// Checks closure, and orientation by the turn at the smallest vertex,
+    /// which is convex whatever the shape of the ring. Rings that fold back on
+    /// themselves there have no area and no orientation.
+    fn assert_normalised(ring: &[(f32, f32)], is_hole: bool) {
+        assert!(ring.len() >= 2, "ring {:?} is not closed", ring);
+        assert_eq!(ring.first(), ring.last(), "ring {:?} is not closed", ring);
+        let mut points = ring[..ring.len() - 1].to_vec();
+        points.dedup();
+        let smallest = (0..points.len())
+            .min_by(|a, b| points[*a].partial_cmp(&points[*b]).unwrap())
+            .unwrap();
+        points.rotate_left(smallest);
+        let (first, second, last) = match points.as_slice() {
+            [first, second, .., last] if points.len() >= 3 => (*first, *second, *last),
+            _ => return,
+        };
+        let turn =
+            (first.0 - last.0) * (second.1 - first.1) - (first.1 - last.1) * (second.0 - first.0);
+        if turn == 0.0 {
+            return;
+        }
+        if is_hole {
+            assert!(turn < 0.0, "hole {:?} runs counter-clockwise", ring);
+        } else {
+            assert!(turn > 0.0, "outer ring {:?} runs clockwise", ring);
+        }
+    }
+
+    #[test]
+    fn test_normalise_ring_reverses_clockwise_outer_ring() {
+        let ring = vec![(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)];
+
+        assert_eq!(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            normalise_ring(&ring, false)
+        );
+        assert_eq!(
+            vec![(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)],
+            normalise_ring(&ring, true)
+        );
+    }
+
+    #[test]
+    fn test_stable_id_depends_only_on_the_cell() {
+        assert_eq!(stable_id(10.2, 20.4, 1.0), stable_id(9.8, 19.6, 1.0));
+        assert_ne!(stable_id(10.0, 20.0, 1.0), stable_id(11.0, 20.0, 1.0));
+        assert_ne!(stable_id(10.0, 20.0, 1.0), stable_id(20.0, 10.0, 1.0));
+        assert_ne!(stable_id(10.0, 20.0, 1.0), stable_id(10.0, 20.0, 0.1));
+    }
+
+    #[test]
+    fn test_settle_id_clashes_keeps_id_for_largest() {
+        let mut ids = vec![7, 3, 7, 7];
+        settle_id_clashes(&mut ids, &[1, 5, 9, 1]);
+
+        assert_eq!(3, ids[1]);
+        assert_eq!(7, ids[2]);
+        assert!(ids[0] != 7 && ids[0] != 3);
+        assert!(ids[3] != 7 && ids[3] != 3 && ids[3] != ids[0]);
+
+        let mut again = vec![7, 3, 7, 7];
+        settle_id_clashes(&mut again, &[1, 5, 9, 1]);
+        assert_eq!(ids, again);
+    }
+
+    #[test]
+    fn test_normalise_ring_orients_traced_border_and_hole() {
+        let image = gray_image!(type: u32,
+            1, 1, 1;
+            1, 0, 1;
+            1, 1, 1);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            Luma([0u32; 1]),
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![
+                vec![(0.0, 0.0), (3.0, 0.0), (3.0, 3.0), (0.0, 3.0), (0.0, 0.0)],
+                vec![(1.0, 1.0), (1.0, 2.0), (2.0, 2.0), (2.0, 1.0), (1.0, 1.0)],
+            ],
+            hierarchy
+                .contours
+                .iter()
+                .zip(&hierarchy.nodes)
+                .map(|(contour, node)| normalise_ring(&to_floats(contour), node.is_hole))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_every_ring_from_each_tracing_is_normalised() {
+        let background_color = Luma([0u32; 1]);
+        let tracings = [Tracing::PixelCentres, Tracing::Cracks, Tracing::PixelWalk];
+        for image in examples() {
+            for tracing in tracings.iter() {
+                let hierarchy = find_contour_hierarchy_in_luma(
+                    background_color,
+                    &image,
+                    *tracing,
+                    Connectivity::Four,
+                );
+                for (contour, node) in hierarchy.contours.iter().zip(&hierarchy.nodes) {
+                    let ring = normalise_ring(&to_floats(contour), node.is_hole);
+                    assert_normalised(&ring, node.is_hole);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 extern crate console_error_panic_hook;
 extern crate image;
-#[macro_use]
+#[cfg_attr(test, macro_use)]
 extern crate imageproc;
 extern crate js_sys;
 extern crate wasm_bindgen;
@@ -16,21 +16,236 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+mod binarisation;
+mod geometry;
 mod region_labelling;
+mod simplify;
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ContourTracing {
+    #[default]
+    PixelCentres,
+    Cracks,
+    PixelWalk,
+    MarchingSquares,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl From<Connectivity> for imageproc::region_labelling::Connectivity {
+    fn from(connectivity: Connectivity) -> Self {
+        match connectivity {
+            Connectivity::Four => imageproc::region_labelling::Connectivity::Four,
+            Connectivity::Eight => imageproc::region_labelling::Connectivity::Eight,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Units {
+    #[default]
+    DevicePixels,
+    Output,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SimplificationMode {
+    #[default]
+    Rings,
+    SharedArcs,
+}
+
+/// Morphological operations on the streets, the background of the mask, applied
+/// before labelling.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Morphology {
+    #[default]
+    None,
+    Dilate,
+    Erode,
+    /// Dilate then erode, which fills gaps in streets narrower than the element.
+    Close,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StructuringElement {
+    #[default]
+    Square,
+    Diamond,
+}
+
+/// How the input is split into streets and biscuits before labelling.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Binarisation {
+    /// Streets are pixels close to the background colour.
+    #[default]
+    BackgroundColour,
+    /// Streets are pixels at least as bright as a fixed luminance.
+    Threshold,
+    /// Streets are pixels brighter than the luminance that best separates the
+    /// image into two classes, by Otsu's method.
+    Otsu,
+    /// Each pixel takes the class of the first palette colour it is close to,
+    /// or is land if there is none.
+    Palette,
+}
+
+/// What a pixel of the input shows. Only land makes up biscuits.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PixelClass {
+    #[default]
+    Barrier = 0,
+    Water = 1,
+    Land = 2,
+    /// Neither a biscuit nor anything that bounds one, such as labels drawn
+    /// over the streets.
+    Ignore = 3,
+    /// Outside the area being analysed, like beyond the edge of the image.
+    Outside = 4,
+}
+
+/// How far a pixel's colour is from the background colour.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ColourDistance {
+    /// The largest difference in any one channel, alpha included, from 0 to 255.
+    #[default]
+    PerChannel,
+    /// The CIE76 difference in L*a*b*, ignoring alpha, where about 2.3 is just
+    /// noticeable.
+    Perceptual,
+}
 
 #[wasm_bindgen]
 pub struct BiscuitFinder {
+    contour_tracing: ContourTracing,
+    connectivity: Connectivity,
+    binarisation: Binarisation,
+    luminance_threshold: f32,
+    background_colour: Rgba<u8>,
+    colour_tolerance: f32,
+    colour_distance: ColourDistance,
+    palette: Vec<(Rgba<u8>, PixelClass)>,
+    alpha_cutoff: u8,
+    simplification_tolerance: f32,
+    simplification_units: Units,
+    simplification_mode: SimplificationMode,
+    min_area: f32,
+    max_area: f32,
+    area_units: Units,
+    drop_edge_biscuits: bool,
+    merge_area: f32,
+    merge_gap: f32,
+    merge_units: Units,
+    adjacency_graph: bool,
+    colouring: bool,
+    shape_analysis: bool,
+    id_precision: f32,
+    morphology: Morphology,
+    structuring_element: StructuringElement,
+    structuring_radius: u8,
+    chain_coding: bool,
+    normalise_rings: bool,
+    input: Vec<u8>,
     border_indexes: Option<Vec<usize>>,
     border_points: Option<Vec<f32>>,
+    hole_indexes: Option<Vec<usize>>,
+    hole_points: Option<Vec<f32>>,
+    hole_parents: Option<Vec<usize>>,
+    hierarchy: Option<Vec<i32>>,
+    chain_starts: Option<Vec<u32>>,
+    chain_indexes: Option<Vec<usize>>,
+    chain_codes: Option<Vec<u8>>,
+    chain_connectivity: Connectivity,
+    areas: Option<Vec<f32>>,
+    perimeters: Option<Vec<f32>>,
+    centroids: Option<Vec<f32>>,
+    bounding_boxes: Option<Vec<f32>>,
+    ids: Option<Vec<u32>>,
+    raw_moments: Option<Vec<f64>>,
+    central_moments: Option<Vec<f64>>,
+    hu_moments: Option<Vec<f64>>,
+    ellipses: Option<Vec<f32>>,
+    shape_metrics: Option<Vec<f32>>,
+    touches_edge: Option<Vec<u8>>,
+    border_classes: Option<Vec<u8>>,
+    adjacency_edges: Option<Vec<u32>>,
+    adjacency_weights: Option<Vec<f32>>,
+    colours: Option<Vec<u8>>,
 }
 
-use image::{Rgba, RgbaImage};
+use image::Rgba;
 
 impl Default for BiscuitFinder {
     fn default() -> Self {
         BiscuitFinder {
+            contour_tracing: ContourTracing::default(),
+            connectivity: Connectivity::default(),
+            binarisation: Binarisation::default(),
+            luminance_threshold: 128.0,
+            background_colour: Rgba([255u8; 4]),
+            colour_tolerance: 0.0,
+            colour_distance: ColourDistance::default(),
+            palette: Vec::new(),
+            alpha_cutoff: 0,
+            simplification_tolerance: 0.0,
+            simplification_units: Units::default(),
+            simplification_mode: SimplificationMode::default(),
+            min_area: 0.0,
+            max_area: f32::INFINITY,
+            area_units: Units::default(),
+            drop_edge_biscuits: false,
+            merge_area: 0.0,
+            merge_gap: 0.0,
+            merge_units: Units::default(),
+            adjacency_graph: false,
+            colouring: false,
+            shape_analysis: false,
+            id_precision: 1.0,
+            morphology: Morphology::default(),
+            structuring_element: StructuringElement::default(),
+            structuring_radius: 1,
+            chain_coding: false,
+            normalise_rings: false,
+            input: Vec::new(),
             border_indexes: None,
             border_points: None,
+            hole_indexes: None,
+            hole_points: None,
+            hole_parents: None,
+            hierarchy: None,
+            chain_starts: None,
+            chain_indexes: None,
+            chain_codes: None,
+            chain_connectivity: Connectivity::default(),
+            areas: None,
+            perimeters: None,
+            centroids: None,
+            bounding_boxes: None,
+            ids: None,
+            raw_moments: None,
+            central_moments: None,
+            hu_moments: None,
+            ellipses: None,
+            shape_metrics: None,
+            touches_edge: None,
+            border_classes: None,
+            adjacency_edges: None,
+            adjacency_weights: None,
+            colours: None,
         }
     }
 }
@@ -44,6 +259,172 @@ impl BiscuitFinder {
         Self::default()
     }
 
+    pub fn set_contour_tracing(&mut self, tracing: ContourTracing) {
+        self.contour_tracing = tracing;
+    }
+
+    /// Whether pixels that touch only at a corner belong to the same biscuit. The
+    /// same rule is used for labelling and for tracing, so each biscuit gets
+    /// exactly one outer contour.
+    pub fn set_connectivity(&mut self, connectivity: Connectivity) {
+        self.connectivity = connectivity;
+    }
+
+    /// Simplifies every ring with Ramer–Douglas–Peucker before it is stored, so
+    /// no dropped point is further than `tolerance` from the outline. The
+    /// tolerance is measured in input pixels or, with `Units::Output`,
+    /// after `x_offset`, `y_offset` and `scale_down` are applied. A tolerance of
+    /// zero keeps every point.
+    pub fn set_simplification_tolerance(&mut self, tolerance: f32, units: Units) {
+        self.simplification_tolerance = tolerance;
+        self.simplification_units = units;
+    }
+
+    /// Drops biscuits whose area is outside `min_area..=max_area` before their
+    /// contours are traced. Areas are in input pixels or, with `Units::Output`,
+    /// scaled down by the square of `scale_down`.
+    pub fn set_area_range(&mut self, min_area: f32, max_area: f32, units: Units) {
+        self.min_area = min_area;
+        self.max_area = max_area;
+        self.area_units = units;
+    }
+
+    /// Merges each biscuit smaller than `area` into the neighbour it shares the
+    /// longest boundary with, filling the line between them, before any are
+    /// dropped by area. Only neighbours at least `area` in size and at most
+    /// `max_gap` apart are considered, so small biscuits never merge into each
+    /// other and lines wider than that are kept. `area` is measured as in
+    /// `set_area_range` and `max_gap` is in input pixels or, with
+    /// `Units::Output`, scaled down by `scale_down`.
+    pub fn set_merge_below_area(&mut self, area: f32, max_gap: f32, units: Units) {
+        self.merge_area = area;
+        self.merge_gap = max_gap;
+        self.merge_units = units;
+    }
+
+    /// Also build the graph of which biscuits neighbour each other, with every
+    /// street pixel counted towards the biscuit it is closest to.
+    pub fn set_adjacency_graph(&mut self, enabled: bool) {
+        self.adjacency_graph = enabled;
+    }
+
+    /// Also give every biscuit a small colour index that none of its neighbours
+    /// in the adjacency graph share.
+    pub fn set_colouring(&mut self, enabled: bool) {
+        self.colouring = enabled;
+    }
+
+    /// Also describe the shape of each biscuit, as its moments, equivalent
+    /// ellipse and shape metrics.
+    pub fn set_shape_analysis(&mut self, enabled: bool) {
+        self.shape_analysis = enabled;
+    }
+
+    /// How far, in output units, a biscuit's centroid can move between redraws
+    /// while it usually keeps the same ID. IDs are derived from the centroid
+    /// snapped to a grid this size, so one can still change when the centroid
+    /// crosses a grid line.
+    pub fn set_id_precision(&mut self, precision: f32) {
+        self.id_precision = precision;
+    }
+
+    /// Drops biscuits with any pixel on the edge of the image, which are usually
+    /// blocks cut off by the bounding box, before their contours are traced.
+    pub fn set_drop_edge_biscuits(&mut self, enabled: bool) {
+        self.drop_edge_biscuits = enabled;
+    }
+
+    /// Cleans up the streets before biscuits are found, using a square or
+    /// diamond of pixels within `radius` of the centre.
+    pub fn set_morphology(
+        &mut self,
+        morphology: Morphology,
+        element: StructuringElement,
+        radius: u8,
+    ) {
+        self.morphology = morphology;
+        self.structuring_element = element;
+        self.structuring_radius = radius;
+    }
+
+    /// With `SimplificationMode::SharedArcs`, each biscuit's border takes in the
+    /// streets nearer to it than to any other biscuit, so biscuits on either
+    /// side of a street meet along its middle. Borders are split wherever three
+    /// or more of these areas meet and each piece is simplified once, so the
+    /// biscuits still meet exactly. Holes only remain around biscuits inside
+    /// them. It needs `ContourTracing::Cracks`, and finding biscuits fails with
+    /// any other contour tracing.
+    pub fn set_simplification_mode(&mut self, mode: SimplificationMode) {
+        self.simplification_mode = mode;
+    }
+
+    /// Also describe every contour as a Freeman chain code of the pixel walk
+    /// around it, with four or eight directions to match the connectivity.
+    /// Chains walk the pixels the outlines enclose, so with
+    /// `SimplificationMode::SharedArcs` they include the streets given to
+    /// each biscuit.
+    pub fn set_chain_coding(&mut self, enabled: bool) {
+        self.chain_coding = enabled;
+    }
+
+    /// How to tell streets from biscuits. `threshold` is the luminance, from 0
+    /// to 255, below which a pixel is biscuit, and is used by
+    /// `Binarisation::Threshold`. With `ContourTracing::MarchingSquares` pixels
+    /// are always split by luminance, at `threshold` or at the level Otsu's
+    /// method finds, and outlines are interpolated where the luminance crosses
+    /// that same threshold.
+    pub fn set_binarisation(&mut self, binarisation: Binarisation, threshold: f32) {
+        self.binarisation = binarisation;
+        self.luminance_threshold = threshold;
+    }
+
+    /// The colour of the streets in the input, and how far from it a pixel's
+    /// colour can be while still counting as street, so that antialiased edges
+    /// do not become biscuits. The colour is only used by
+    /// `Binarisation::BackgroundColour`, but the tolerance also applies to the
+    /// palette.
+    pub fn set_background_colour(
+        &mut self,
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+        tolerance: f32,
+        distance: ColourDistance,
+    ) {
+        self.background_colour = Rgba([r, g, b, a]);
+        self.colour_tolerance = tolerance;
+        self.colour_distance = distance;
+    }
+
+    /// Adds a colour to the palette used by `Binarisation::Palette`. Pixels are
+    /// matched to palette colours with the tolerance and distance given to
+    /// `set_background_colour`, in the order the colours were added.
+    pub fn add_palette_colour(&mut self, r: u8, g: u8, b: u8, a: u8, class: PixelClass) {
+        self.palette.push((Rgba([r, g, b, a]), class));
+    }
+
+    pub fn clear_palette(&mut self) {
+        self.palette.clear();
+    }
+
+    /// Pixels with alpha below `cutoff` are outside the area being analysed,
+    /// whatever their colour, and are given `PixelClass::Outside`. Biscuits next
+    /// to them are treated as being on the edge of the image, and the
+    /// adjacency graph does not cross them. They are left out when Otsu's
+    /// method picks a threshold, and marching squares treats them as street
+    /// at full luminance whatever their colour. 0 turns this off.
+    pub fn set_alpha_cutoff(&mut self, cutoff: u8) {
+        self.alpha_cutoff = cutoff;
+    }
+
+    /// Orient outer rings counter-clockwise and holes clockwise, by the sign of
+    /// their shoelace area in output coordinates, and repeat the first point of
+    /// every ring at its end, as GeoJSON expects.
+    pub fn set_normalise_rings(&mut self, enabled: bool) {
+        self.normalise_rings = enabled;
+    }
+
     pub fn find_biscuits(
         &mut self,
         width: u32,
@@ -53,55 +434,411 @@ impl BiscuitFinder {
         y_offset: f32,
         scale_down: f32,
     ) -> Result<String, JsValue> {
-        use image::{GrayImage, Luma};
+        self.find_biscuits_in_buffer(
+            &input.0,
+            width * 4,
+            0,
+            0,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            scale_down,
+        )
+    }
+
+    /// Sizes the input buffer kept in wasm memory to `len` bytes, keeping what
+    /// is already in it. Write RGBA image data into it through `input_ptr`,
+    /// then search it with `find_biscuits_in_region` as often as needed
+    /// without copying it across again.
+    pub fn resize_input(&mut self, len: usize) {
+        self.input.resize(len, 0);
+    }
+
+    /// Where the input buffer starts in wasm memory. It moves whenever
+    /// `resize_input` grows the buffer.
+    pub fn input_ptr(&mut self) -> *mut u8 {
+        self.input.as_mut_ptr()
+    }
+
+    /// Like `find_biscuits`, but only looks at the `width` by `height` window
+    /// at `(x, y)` of the input buffer, holding a larger RGBA image whose rows
+    /// are `stride` bytes apart, such as the image data of the whole canvas.
+    /// Neither the crop nor the canvas is copied, so the cost follows the
+    /// window. Pixel coordinates are relative to the window.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_biscuits_in_region(
+        &mut self,
+        stride: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        x_offset: f32,
+        y_offset: f32,
+        scale_down: f32,
+    ) -> Result<String, JsValue> {
+        let input = std::mem::take(&mut self.input);
+        let result = self.find_biscuits_in_buffer(
+            &input, stride, x, y, width, height, x_offset, y_offset, scale_down,
+        );
+        self.input = input;
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn find_biscuits_in_buffer(
+        &mut self,
+        input: &[u8],
+        stride: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        x_offset: f32,
+        y_offset: f32,
+        scale_down: f32,
+    ) -> Result<String, JsValue> {
+        use image::{GenericImageView, GrayImage, ImageBuffer, Luma, Pixel};
         use imageproc::definitions::Image;
         use imageproc::map::map_colors;
-        use imageproc::region_labelling::{connected_components, Connectivity};
-        let input_background_color = Rgba([255u8; 4]);
+        use imageproc::region_labelling::connected_components;
 
-        match RgbaImage::from_raw(width, height, input.0) {
-            Some(image) => {
+        if self.simplification_mode == SimplificationMode::SharedArcs
+            && self.contour_tracing != ContourTracing::Cracks
+        {
+            return Err("shared arcs need crack contour tracing".into());
+        }
+        let is_empty = width == 0 || height == 0;
+        let is_bad_stride = stride & 3 != 0 || u64::from(stride) < u64::from(width) * 4;
+        let buffer = if is_empty {
+            ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(width, height, &input[..0])
+        } else if is_bad_stride {
+            None
+        } else {
+            let rows = (input.len() / stride as usize) as u32;
+            ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(stride / 4, rows, input)
+        };
+        let (x, y) = if is_empty { (0, 0) } else { (x, y) };
+        let in_bounds = |buffer: &ImageBuffer<Rgba<u8>, &[u8]>| {
+            x as u64 + width as u64 <= buffer.width() as u64
+                && y as u64 + height as u64 <= buffer.height() as u64
+        };
+        match buffer.filter(in_bounds) {
+            Some(buffer) => {
+                let image = buffer.view(x, y, width, height);
                 let foreground_color = Luma([255u8; 1]);
                 let background_color = Luma([0u8; 1]);
 
-                let gray_image: GrayImage = map_colors(&image, |p| {
-                    if p == input_background_color {
-                        background_color
-                    } else {
+                let mut levels: Option<GrayImage> = match (self.contour_tracing, self.binarisation)
+                {
+                    (ContourTracing::MarchingSquares, _)
+                    | (_, Binarisation::Threshold)
+                    | (_, Binarisation::Otsu) => {
+                        Some(binarisation::map_pixels(&image, |p| p.to_luma()))
+                    }
+                    _ => None,
+                };
+                let threshold = match (&levels, self.binarisation) {
+                    (Some(levels), Binarisation::Otsu) => {
+                        let mut histogram = [0u32; 256];
+                        for (x, y, level) in levels.enumerate_pixels() {
+                            if image.get_pixel(x, y)[3] >= self.alpha_cutoff {
+                                histogram[level[0] as usize] += 1;
+                            }
+                        }
+                        binarisation::otsu_level(&histogram) as f32 + 0.5
+                    }
+                    _ => self.luminance_threshold,
+                };
+                let class_of = |class: PixelClass| Luma([class as u8]);
+                let mut classes: GrayImage = match &levels {
+                    Some(levels) => map_colors(levels, |p| {
+                        if (p[0] as f32) < threshold {
+                            class_of(PixelClass::Land)
+                        } else {
+                            class_of(PixelClass::Barrier)
+                        }
+                    }),
+                    None => match self.binarisation {
+                        Binarisation::Palette => {
+                            let palette: Vec<_> = self
+                                .palette
+                                .iter()
+                                .map(|(colour, class)| (self.colour_matcher(*colour), *class))
+                                .collect();
+                            binarisation::map_pixels(&image, |p| {
+                                let class = palette
+                                    .iter()
+                                    .find(|(matches, _)| matches(p))
+                                    .map_or(PixelClass::Land, |(_, class)| *class);
+                                class_of(class)
+                            })
+                        }
+                        _ => {
+                            let is_background = self.colour_matcher(self.background_colour);
+                            binarisation::map_pixels(&image, |p| {
+                                if is_background(p) {
+                                    class_of(PixelClass::Barrier)
+                                } else {
+                                    class_of(PixelClass::Land)
+                                }
+                            })
+                        }
+                    },
+                };
+                if self.alpha_cutoff > 0 {
+                    for (x, y, class) in classes.enumerate_pixels_mut() {
+                        if image.get_pixel(x, y)[3] < self.alpha_cutoff {
+                            *class = class_of(PixelClass::Outside);
+                            if let Some(levels) = levels.as_mut() {
+                                levels.put_pixel(x, y, Luma([u8::MAX]));
+                            }
+                        }
+                    }
+                }
+                let mut gray_image: GrayImage = map_colors(&classes, |p| {
+                    if p == class_of(PixelClass::Land) {
                         foreground_color
+                    } else {
+                        background_color
                     }
                 });
+                self.apply_morphology(&mut gray_image);
 
                 BiscuitFinder::time_with_label("connected_components");
-                let labelled_image: Image<Luma<u32>> =
-                    connected_components(&gray_image, Connectivity::Four, background_color);
+                let connectivity = self.connectivity.into();
+                let mut labelled_image: Image<Luma<u32>> =
+                    connected_components(&gray_image, connectivity, background_color);
                 BiscuitFinder::time_end_with_label("connected_components");
+                let area_scale = |units: Units| match units {
+                    Units::DevicePixels => 1.0,
+                    Units::Output => scale_down * scale_down,
+                };
+                if self.merge_area > 0.0 {
+                    BiscuitFinder::time_with_label("merge_small_regions");
+                    let min_area = (self.merge_area * area_scale(self.merge_units)).ceil();
+                    let max_gap = match self.merge_units {
+                        Units::DevicePixels => self.merge_gap,
+                        Units::Output => self.merge_gap * scale_down,
+                    };
+                    region_labelling::adjacency::merge_small_regions(
+                        Luma([0u32; 1]),
+                        &mut labelled_image,
+                        min_area as u32,
+                        max_gap.floor() as u32,
+                        |x, y| classes.get_pixel(x, y) == &class_of(PixelClass::Outside),
+                    );
+                    BiscuitFinder::time_end_with_label("merge_small_regions");
+                }
+                let transform =
+                    |x: f32, y: f32| (x_offset + (x / scale_down), y_offset + (y / scale_down));
+                BiscuitFinder::time_with_label("region_properties");
+                let mut properties = region_labelling::properties::region_properties(
+                    Luma([0u32; 1]),
+                    &labelled_image,
+                );
+                let mut border_classes = region_labelling::properties::border_classes(
+                    Luma([0u32; 1]),
+                    &labelled_image,
+                    &classes,
+                );
+                border_classes.resize(properties.len(), 0);
+                let mut touches_edge: Vec<bool> = properties
+                    .iter()
+                    .zip(&border_classes)
+                    .map(|(region, classes)| {
+                        region.touches_edge(width, height)
+                            || classes & (1 << PixelClass::Outside as u8) != 0
+                    })
+                    .collect();
+                let area_scale = area_scale(self.area_units);
+                let area_range = (self.min_area * area_scale)..=(self.max_area * area_scale);
+                let keep: Vec<bool> = properties
+                    .iter()
+                    .zip(&touches_edge)
+                    .map(|(region, touches_edge)| {
+                        area_range.contains(&(region.area as f32))
+                            && !(self.drop_edge_biscuits && *touches_edge)
+                    })
+                    .collect();
+                if keep.contains(&false) {
+                    region_labelling::retain_regions(Luma([0u32; 1]), &mut labelled_image, &keep);
+                    let mut kept = keep.iter();
+                    properties.retain(|_| *kept.next().unwrap());
+                    let mut kept = keep.iter();
+                    border_classes.retain(|_| *kept.next().unwrap());
+                    let mut kept = keep.iter();
+                    touches_edge.retain(|_| *kept.next().unwrap());
+                }
+                let pixel_origin = match self.contour_tracing {
+                    ContourTracing::PixelCentres | ContourTracing::PixelWalk => -0.5,
+                    ContourTracing::Cracks | ContourTracing::MarchingSquares => 0.0,
+                };
+                self.store_properties(&properties, scale_down, |x, y| {
+                    transform(x + pixel_origin, y + pixel_origin)
+                });
+                self.touches_edge = Some(touches_edge.into_iter().map(u8::from).collect());
+                self.border_classes = Some(border_classes);
+                BiscuitFinder::time_end_with_label("region_properties");
+                let edges = if self.adjacency_graph || self.colouring {
+                    BiscuitFinder::time_with_label("adjacency_graph");
+                    let edges = region_labelling::adjacency::region_adjacency_graph(
+                        Luma([0u32; 1]),
+                        &labelled_image,
+                        |x, y| classes.get_pixel(x, y) == &class_of(PixelClass::Outside),
+                    );
+                    BiscuitFinder::time_end_with_label("adjacency_graph");
+                    Some(edges)
+                } else {
+                    None
+                };
+                match (&edges, self.adjacency_graph) {
+                    (Some(edges), true) => self.store_adjacency_graph(edges, scale_down),
+                    _ => {
+                        self.adjacency_edges = None;
+                        self.adjacency_weights = None;
+                    }
+                }
+                self.colours = match (&edges, self.colouring) {
+                    (Some(edges), true) => Some(region_labelling::adjacency::colour_regions(
+                        properties.len(),
+                        edges,
+                    )),
+                    _ => None,
+                };
+                if self.shape_analysis {
+                    BiscuitFinder::time_with_label("moments");
+                    let moments =
+                        region_labelling::moments::raw_moments(Luma([0u32; 1]), &labelled_image);
+                    self.store_moments(&moments, scale_down);
+                    BiscuitFinder::time_end_with_label("moments");
+                } else {
+                    self.raw_moments = None;
+                    self.central_moments = None;
+                    self.hu_moments = None;
+                    self.ellipses = None;
+                }
+                let tracing = match self.contour_tracing {
+                    ContourTracing::PixelCentres => Some(region_labelling::Tracing::PixelCentres),
+                    ContourTracing::Cracks => Some(region_labelling::Tracing::Cracks),
+                    ContourTracing::PixelWalk => Some(region_labelling::Tracing::PixelWalk),
+                    ContourTracing::MarchingSquares => None,
+                };
+                let traces_cracks = self.simplification_mode == SimplificationMode::Rings
+                    && tracing == Some(region_labelling::Tracing::Cracks);
+                if !self.shape_analysis {
+                    self.shape_metrics = None;
+                } else if !traces_cracks {
+                    BiscuitFinder::time_with_label("shape_metrics");
+                    let cracks = region_labelling::find_contour_hierarchy_in_luma(
+                        Luma([0u32; 1]),
+                        &labelled_image,
+                        region_labelling::Tracing::Cracks,
+                        connectivity,
+                    );
+                    self.store_shape_metrics(&properties, &cracks, scale_down);
+                    BiscuitFinder::time_end_with_label("shape_metrics");
+                }
                 BiscuitFinder::time_with_label("find_contours");
-                let contours =
-                    region_labelling::find_contours_in_luma(Luma([0u32; 1]), &labelled_image);
-                BiscuitFinder::time_end_with_label("find_contours");
-                let mut border_indexes = Vec::new();
-                let mut border_points = Vec::new();
-                let mut start_index: usize = 0;
-                for contour in contours {
-                    let indexes_used = contour.len() * 2;
-                    border_indexes.push(start_index + indexes_used);
-                    start_index += indexes_used;
-                    for point in contour {
-                        let x = x_offset + (point.x as f32 / scale_down);
-                        let y = y_offset + (point.y as f32 / scale_down);
-                        border_points.push(x);
-                        border_points.push(y);
+                let tolerance = match self.simplification_units {
+                    Units::DevicePixels => self.simplification_tolerance / scale_down,
+                    Units::Output => self.simplification_tolerance,
+                };
+                let shared_areas = match self.simplification_mode {
+                    SimplificationMode::SharedArcs => {
+                        Some(region_labelling::adjacency::nearest_region_labels(
+                            Luma([0u32; 1]),
+                            &labelled_image,
+                            |x, y| classes.get_pixel(x, y) == &class_of(PixelClass::Outside),
+                        ))
                     }
+                    SimplificationMode::Rings => None,
+                };
+                match (&shared_areas, tracing) {
+                    (Some(areas), _) => {
+                        let hierarchy = region_labelling::find_contour_hierarchy_in_label_order(
+                            Luma([0u32; 1]),
+                            areas,
+                            region_labelling::Tracing::Cracks,
+                            connectivity,
+                        );
+                        let contours = simplify::simplify_shared_arcs(
+                            &hierarchy.contours,
+                            |point| {
+                                region_labelling::is_arc_end(
+                                    Luma([0u32; 1]),
+                                    areas,
+                                    point.x,
+                                    point.y,
+                                )
+                            },
+                            tolerance * scale_down,
+                        );
+                        let simplified = region_labelling::ContourHierarchy {
+                            contours,
+                            nodes: hierarchy.nodes,
+                        };
+                        self.store_contours(&simplified, 0.0, |(x, y)| transform(*x, *y));
+                    }
+                    (None, Some(tracing)) => {
+                        let hierarchy = region_labelling::find_contour_hierarchy_in_luma(
+                            Luma([0u32; 1]),
+                            &labelled_image,
+                            tracing,
+                            connectivity,
+                        );
+                        if self.shape_analysis && traces_cracks {
+                            self.store_shape_metrics(&properties, &hierarchy, scale_down);
+                        }
+                        self.store_contours(&hierarchy, tolerance, |point| {
+                            transform(point.x as f32, point.y as f32)
+                        });
+                    }
+                    (None, None) => {
+                        let hierarchy = region_labelling::find_iso_contour_hierarchy_in_luma(
+                            Luma([0u32; 1]),
+                            &labelled_image,
+                            levels.as_ref().unwrap(),
+                            threshold,
+                            connectivity,
+                        );
+                        self.store_contours(&hierarchy, tolerance, |point| {
+                            transform(point.x, point.y)
+                        });
+                    }
+                }
+                BiscuitFinder::time_end_with_label("find_contours");
+                if self.chain_coding {
+                    BiscuitFinder::time_with_label("chain_codes");
+                    let walks = match &shared_areas {
+                        Some(areas) => region_labelling::find_contour_hierarchy_in_label_order(
+                            Luma([0u32; 1]),
+                            areas,
+                            region_labelling::Tracing::PixelWalk,
+                            connectivity,
+                        ),
+                        None => region_labelling::find_contour_hierarchy_in_luma(
+                            Luma([0u32; 1]),
+                            &labelled_image,
+                            region_labelling::Tracing::PixelWalk,
+                            connectivity,
+                        ),
+                    };
+                    self.store_chain_codes(&walks, self.connectivity);
+                    BiscuitFinder::time_end_with_label("chain_codes");
+                } else {
+                    self.chain_starts = None;
+                    self.chain_indexes = None;
+                    self.chain_codes = None;
                 }
-                self.border_indexes = Some(border_indexes);
-                self.border_points = Some(border_points);
                 Ok("processed image".into())
             }
             None => Err("couldn't read from raw".into()),
         }
     }
 
+    #[cfg_attr(not(feature = "console_tracing"), allow(unused_variables))]
     fn time_with_label(message: &str) {
         #[cfg(feature = "console_tracing")]
         {
@@ -110,6 +847,7 @@ impl BiscuitFinder {
         }
     }
 
+    #[cfg_attr(not(feature = "console_tracing"), allow(unused_variables))]
     fn time_end_with_label(message: &str) {
         #[cfg(feature = "console_tracing")]
         {
@@ -145,154 +883,1780 @@ impl BiscuitFinder {
             None => panic!("no border points"),
         }
     }
-}
 
-impl BiscuitFinder {
-    pub fn border_indexes(&self) -> Result<Vec<usize>, String> {
-        match &self.border_indexes {
-            Some(vec) => Ok(vec.clone()),
-            None => panic!("no border points"),
+    pub fn hole_indexes_ptr(&self) -> *const usize {
+        match &self.hole_indexes {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no hole indexes"),
         }
     }
 
-    pub fn border_points(&self) -> Result<Vec<f32>, String> {
-        match &self.border_points {
-            Some(vec) => Ok(vec.clone()),
-            None => panic!("no border points"),
+    pub fn num_holes(&self) -> usize {
+        match &self.hole_indexes {
+            Some(vec) => vec.len(),
+            None => panic!("no holes"),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    extern crate wasm_bindgen_test;
-    use super::*;
-    use wasm_bindgen_test::*;
+    pub fn hole_points_ptr(&self) -> *const f32 {
+        match &self.hole_points {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no hole points"),
+        }
+    }
 
-    #[wasm_bindgen_test]
-    fn test_when_only_background_pixels_provided() {
-        let mut biscuit_finder = BiscuitFinder::new();
+    pub fn num_hole_points(&self) -> usize {
+        match &self.hole_points {
+            Some(vec) => vec.len() / 2,
+            None => panic!("no hole points"),
+        }
+    }
 
-        let image = rgba_image!(
-            [255, 255, 255, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [255, 255, 255, 255]);
+    pub fn hole_parents_ptr(&self) -> *const usize {
+        match &self.hole_parents {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no hole parents"),
+        }
+    }
 
-        let input = Clamped(image.to_vec());
-        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+    /// Four entries per contour, `[next, previous, first_child, parent]`, with -1
+    /// for no link. Contours are numbered borders first, then holes.
+    pub fn hierarchy_ptr(&self) -> *const i32 {
+        match &self.hierarchy {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no hierarchy"),
+        }
+    }
 
-        assert_eq!(Ok("processed image".into()), result);
+    pub fn num_biscuits(&self) -> usize {
+        match &self.areas {
+            Some(vec) => vec.len(),
+            None => panic!("no biscuits"),
+        }
+    }
 
-        assert_eq!(0, biscuit_finder.num_borders());
-        let border_points = biscuit_finder.border_points();
-        assert_eq!(Ok(vec![]), border_points);
-        let border_indexes = biscuit_finder.border_indexes();
-        assert_eq!(Ok(vec![]), border_indexes);
+    /// One entry per biscuit, in the same order as the borders, scaled down
+    /// by the square of `scale_down`.
+    pub fn areas_ptr(&self) -> *const f32 {
+        match &self.areas {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no areas"),
+        }
     }
 
-    #[wasm_bindgen_test]
-    fn test_with_single_pixel_biscuit_in_top_left_corner() {
-        let mut biscuit_finder = BiscuitFinder::new();
+    /// One entry per biscuit: the length of the pixel edges around it, holes
+    /// included, scaled down by `scale_down`.
+    pub fn perimeters_ptr(&self) -> *const f32 {
+        match &self.perimeters {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no perimeters"),
+        }
+    }
 
-        let image = rgba_image!(
-            [0,     0,   0, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [255, 255, 255, 255]);
+    /// Two entries per biscuit, `[x, y]`, treating each pixel as a unit square
+    /// placed as the contour tracing places it: centred on `(x, y)` with
+    /// `ContourTracing::PixelCentres` and `ContourTracing::PixelWalk`, and
+    /// from `(x, y)` to `(x + 1, y + 1)` otherwise, so centroids match the
+    /// outlines.
+    pub fn centroids_ptr(&self) -> *const f32 {
+        match &self.centroids {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no centroids"),
+        }
+    }
 
-        let input = Clamped(image.to_vec());
-        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+    /// Four entries per biscuit, `[min_x, min_y, max_x, max_y]`, enclosing
+    /// every pixel of it, with pixels placed as for `centroids_ptr`.
+    pub fn bounding_boxes_ptr(&self) -> *const f32 {
+        match &self.bounding_boxes {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no bounding boxes"),
+        }
+    }
 
-        assert_eq!(Ok("processed image".into()), result);
+    /// Ten entries per biscuit, `[m00, m10, m01, m20, m11, m02, m30, m21, m12,
+    /// m03]`, with `x` and `y` the input pixel indexes.
+    pub fn raw_moments_ptr(&self) -> *const f64 {
+        match &self.raw_moments {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no raw moments"),
+        }
+    }
 
-        assert_eq!(1, biscuit_finder.num_borders());
-        let border_points = biscuit_finder.border_points();
-        assert_eq!(Ok(vec![0.0, 0.0]), border_points);
-        let border_indexes = biscuit_finder.border_indexes();
-        assert_eq!(Ok(vec![2]), border_indexes);
+    /// Seven entries per biscuit, `[mu20, mu11, mu02, mu30, mu21, mu12, mu03]`,
+    /// in input pixels.
+    pub fn central_moments_ptr(&self) -> *const f64 {
+        match &self.central_moments {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no central moments"),
+        }
     }
 
-    #[wasm_bindgen_test]
-    fn test_with_big_biscuit_in_middle() {
-        let mut biscuit_finder = BiscuitFinder::new();
+    /// Seven entries per biscuit, the Hu invariants in their usual order.
+    pub fn hu_moments_ptr(&self) -> *const f64 {
+        match &self.hu_moments {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no hu moments"),
+        }
+    }
 
-        let image = rgba_image!(
-            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+    /// Four entries per biscuit, `[major_axis_length, minor_axis_length,
+    /// orientation, eccentricity]`, for the ellipse with the same second
+    /// moments. Lengths are scaled down by `scale_down`; orientation is in
+    /// radians from the `x` axis towards the `y` axis.
+    pub fn ellipses_ptr(&self) -> *const f32 {
+        match &self.ellipses {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no ellipses"),
+        }
+    }
 
-        let input = Clamped(image.to_vec());
-        let result = biscuit_finder.find_biscuits(4, 4, input, 0.0, 0.0, 1.0);
+    /// Four entries per biscuit, `[polsby_popper, hull_area, solidity,
+    /// convexity]`, when shape analysis is on. Polsby–Popper is measured on the
+    /// outer border with its pixel corners cut diagonally, so discs score near
+    /// 1 and squares near π/4. The hull is that of the pixel squares, and its
+    /// area is scaled down by the square of `scale_down`.
+    pub fn shape_metrics_ptr(&self) -> *const f32 {
+        match &self.shape_metrics {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no shape metrics"),
+        }
+    }
 
-        assert_eq!(Ok("processed image".into()), result);
+    /// One entry per biscuit, 1 if any of its pixels is on the edge of the
+    /// image or next to a pixel below the alpha cutoff, and 0 otherwise.
+    pub fn touches_edge_ptr(&self) -> *const u8 {
+        match &self.touches_edge {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no touches edge flags"),
+        }
+    }
 
-        assert_eq!(1, biscuit_finder.num_borders());
-        let border_points = biscuit_finder.border_points();
-        assert_eq!(
-            Ok(vec![1.0, 1.0, 2.0, 1.0, 2.0, 2.0, 1.0, 2.0]),
-            border_points
-        );
-        let border_indexes = biscuit_finder.border_indexes();
-        assert_eq!(Ok(vec![8]), border_indexes);
+    /// One entry per biscuit, with bit `1 << class` set for each `PixelClass`
+    /// found just outside it. Land shows up where morphology has widened the
+    /// streets.
+    pub fn border_classes_ptr(&self) -> *const u8 {
+        match &self.border_classes {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no border classes"),
+        }
     }
 
-    #[wasm_bindgen_test]
-    fn test_with_multiple_single_pixel_biscuits() {
-        let mut biscuit_finder = BiscuitFinder::new();
+    /// Two entries per edge of the adjacency graph, the indexes of the two
+    /// biscuits, smaller first.
+    pub fn adjacency_edges_ptr(&self) -> *const u32 {
+        match &self.adjacency_edges {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no adjacency edges"),
+        }
+    }
 
-        let image = rgba_image!(
-            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
-            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+    pub fn num_adjacency_edges(&self) -> usize {
+        match &self.adjacency_edges {
+            Some(vec) => vec.len() / 2,
+            None => panic!("no adjacency edges"),
+        }
+    }
 
-        let input = Clamped(image.to_vec());
-        let result = biscuit_finder.find_biscuits(5, 5, input, 0.0, 0.0, 1.0);
+    /// Two entries per edge of the adjacency graph, `[shared_length,
+    /// separation]`: how long the frontage between the two biscuits is, and how
+    /// far apart they are at the closest, both scaled down by `scale_down`.
+    pub fn adjacency_weights_ptr(&self) -> *const f32 {
+        match &self.adjacency_weights {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no adjacency weights"),
+        }
+    }
 
-        assert_eq!(Ok("processed image".into()), result);
+    /// One entry per biscuit, a colour index that differs from those of all
+    /// its neighbours.
+    pub fn colours_ptr(&self) -> *const u8 {
+        match &self.colours {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no colours"),
+        }
+    }
 
-        assert_eq!(4, biscuit_finder.num_borders());
-        let border_points = biscuit_finder.border_points();
-        assert_eq!(
-            Ok(vec![
-                1.0, 1.0, //
-                3.0, 1.0, //
-                1.0, 3.0, //
-                3.0, 3.0, //
-            ]),
-            border_points
-        );
+    /// One entry per biscuit, an ID that stays the same from one call to the
+    /// next while the biscuit's centroid in output coordinates stays put. When
+    /// biscuits share a centroid, the largest keeps the ID and the others get
+    /// new ones derived from it.
+    pub fn ids_ptr(&self) -> *const u32 {
+        match &self.ids {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no ids"),
+        }
+    }
+
+    /// Two entries per contour, the `x` and `y` of the pixel the chain starts
+    /// at, before any offset or scaling. Contours are numbered as in the
+    /// hierarchy, borders first and then holes.
+    pub fn chain_starts_ptr(&self) -> *const u32 {
+        match &self.chain_starts {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no chain starts"),
+        }
+    }
+
+    pub fn chain_indexes_ptr(&self) -> *const usize {
+        match &self.chain_indexes {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no chain indexes"),
+        }
+    }
+
+    pub fn num_chains(&self) -> usize {
+        match &self.chain_indexes {
+            Some(vec) => vec.len(),
+            None => panic!("no chains"),
+        }
+    }
+
+    /// Freeman codes counter-clockwise from east: `0..4` with four-connectivity
+    /// and `0..8` with eight-connectivity, `y` growing downwards.
+    pub fn chain_codes_ptr(&self) -> *const u8 {
+        match &self.chain_codes {
+            Some(vec) => vec.as_ptr(),
+            None => panic!("no chain codes"),
+        }
+    }
+
+    pub fn num_chain_codes(&self) -> usize {
+        match &self.chain_codes {
+            Some(vec) => vec.len(),
+            None => panic!("no chain codes"),
+        }
+    }
+
+    /// The pixels of the contour whose chain is at `index`, as `x` and `y`
+    /// pairs before any offset or scaling, starting at its chain start.
+    pub fn decode_chain(&self, index: usize) -> Vec<u32> {
+        match (&self.chain_starts, &self.chain_indexes, &self.chain_codes) {
+            (Some(starts), Some(indexes), Some(codes)) => {
+                let begin = if index == 0 { 0 } else { indexes[index - 1] };
+                let chain = region_labelling::chain_code::ChainCode {
+                    start: region_labelling::Point::new(starts[2 * index], starts[2 * index + 1]),
+                    codes: codes[begin..indexes[index]].to_vec(),
+                };
+                region_labelling::chain_code::decode(&chain, self.chain_connectivity.into())
+                    .iter()
+                    .flat_map(|point| vec![point.x, point.y])
+                    .collect()
+            }
+            _ => panic!("no chain codes"),
+        }
+    }
+}
+
+impl BiscuitFinder {
+    /// Whether a pixel is within the colour tolerance of `colour`, by the
+    /// configured colour distance.
+    fn colour_matcher(&self, colour: Rgba<u8>) -> impl Fn(Rgba<u8>) -> bool {
+        let tolerance = self.colour_tolerance;
+        let colour_lab = binarisation::to_lab(colour);
+        let distance = self.colour_distance;
+        move |p| match distance {
+            ColourDistance::PerChannel => {
+                binarisation::per_channel_distance(p, colour) <= tolerance
+            }
+            ColourDistance::Perceptual => {
+                binarisation::perceptual_distance(binarisation::to_lab(p), colour_lab) <= tolerance
+            }
+        }
+    }
+
+    /// Applies the morphology to the streets by applying its dual to the biscuits,
+    /// which are the foreground of `mask`.
+    fn apply_morphology(&self, mask: &mut image::GrayImage) {
+        use imageproc::distance_transform::Norm;
+        use imageproc::morphology::{dilate_mut, erode_mut, open_mut};
+        let norm = match self.structuring_element {
+            StructuringElement::Square => Norm::LInf,
+            StructuringElement::Diamond => Norm::L1,
+        };
+        let radius = self.structuring_radius;
+        match self.morphology {
+            Morphology::None => {}
+            Morphology::Dilate => erode_mut(mask, norm, radius),
+            Morphology::Erode => dilate_mut(mask, norm, radius),
+            Morphology::Close => open_mut(mask, norm, radius),
+        }
+    }
+
+    fn store_contours<P, F>(
+        &mut self,
+        hierarchy: &region_labelling::ContourHierarchy<P>,
+        tolerance: f32,
+        transform: F,
+    ) where
+        F: Fn(&P) -> (f32, f32),
+    {
+        let num_borders = hierarchy.nodes.iter().filter(|node| !node.is_hole).count();
+        let mut ring_indexes = Vec::with_capacity(hierarchy.nodes.len());
+        let (mut next_border, mut next_hole) = (0, num_borders);
+        for node in &hierarchy.nodes {
+            let next = if node.is_hole {
+                &mut next_hole
+            } else {
+                &mut next_border
+            };
+            ring_indexes.push(*next);
+            *next += 1;
+        }
+        let mut border_indexes = Vec::new();
+        let mut border_points = Vec::new();
+        let mut hole_indexes = Vec::new();
+        let mut hole_points = Vec::new();
+        let mut hole_parents = Vec::new();
+        let mut hierarchy_links = vec![-1; hierarchy.nodes.len() * 4];
+        for (contour, node) in hierarchy.contours.iter().zip(&hierarchy.nodes) {
+            let ring = self.prepare_ring(contour, &transform, tolerance, node.is_hole);
+            if node.is_hole {
+                BiscuitFinder::push_ring(&ring, &mut hole_indexes, &mut hole_points);
+                hole_parents.push(ring_indexes[node.parent.unwrap()]);
+            } else {
+                BiscuitFinder::push_ring(&ring, &mut border_indexes, &mut border_points);
+            }
+        }
+        for (index, node) in hierarchy.nodes.iter().enumerate() {
+            let links = [node.next, node.previous, node.first_child, node.parent];
+            let offset = ring_indexes[index] * 4;
+            for (link, value) in links.iter().enumerate() {
+                if let Some(value) = value {
+                    hierarchy_links[offset + link] = ring_indexes[*value] as i32;
+                }
+            }
+        }
+        self.border_indexes = Some(border_indexes);
+        self.border_points = Some(border_points);
+        self.hole_indexes = Some(hole_indexes);
+        self.hole_points = Some(hole_points);
+        self.hole_parents = Some(hole_parents);
+        self.hierarchy = Some(hierarchy_links);
+    }
+
+    fn prepare_ring<P, F>(
+        &self,
+        ring: &[P],
+        transform: &F,
+        tolerance: f32,
+        is_hole: bool,
+    ) -> Vec<(f32, f32)>
+    where
+        F: Fn(&P) -> (f32, f32),
+    {
+        let ring: Vec<(f32, f32)> = ring.iter().map(transform).collect();
+        let ring = simplify::simplify_ring(&ring, tolerance);
+        if self.normalise_rings {
+            geometry::normalise_ring(&ring, is_hole)
+        } else {
+            ring
+        }
+    }
+
+    fn push_ring(ring: &[(f32, f32)], indexes: &mut Vec<usize>, points: &mut Vec<f32>) {
+        for (x, y) in ring {
+            points.push(*x);
+            points.push(*y);
+        }
+        indexes.push(points.len());
+    }
+
+    fn store_properties<F>(
+        &mut self,
+        properties: &[region_labelling::properties::RegionProperties],
+        scale_down: f32,
+        transform: F,
+    ) where
+        F: Fn(f32, f32) -> (f32, f32),
+    {
+        let mut areas = Vec::with_capacity(properties.len());
+        let mut perimeters = Vec::with_capacity(properties.len());
+        let mut centroids = Vec::with_capacity(properties.len() * 2);
+        let mut bounding_boxes = Vec::with_capacity(properties.len() * 4);
+        let mut ids = Vec::with_capacity(properties.len());
+        for region in properties {
+            areas.push(region.area as f32 / (scale_down * scale_down));
+            perimeters.push(region.perimeter as f32 / scale_down);
+            let (x, y) = region.centroid();
+            let (x, y) = transform(x, y);
+            centroids.push(x);
+            centroids.push(y);
+            ids.push(geometry::stable_id(x, y, self.id_precision));
+            let (min_x, min_y) = transform(region.min_x as f32, region.min_y as f32);
+            let (max_x, max_y) = transform(region.max_x as f32, region.max_y as f32);
+            bounding_boxes.extend_from_slice(&[min_x, min_y, max_x, max_y]);
+        }
+        self.areas = Some(areas);
+        self.perimeters = Some(perimeters);
+        self.centroids = Some(centroids);
+        self.bounding_boxes = Some(bounding_boxes);
+        let sizes: Vec<u32> = properties.iter().map(|region| region.area).collect();
+        geometry::settle_id_clashes(&mut ids, &sizes);
+        self.ids = Some(ids);
+    }
+
+    fn store_moments(
+        &mut self,
+        moments: &[region_labelling::moments::RawMoments],
+        scale_down: f32,
+    ) {
+        let mut raw_moments = Vec::with_capacity(moments.len() * 10);
+        let mut central_moments = Vec::with_capacity(moments.len() * 7);
+        let mut hu_moments = Vec::with_capacity(moments.len() * 7);
+        let mut ellipses = Vec::with_capacity(moments.len() * 4);
+        for raw in moments {
+            raw_moments.extend_from_slice(&[
+                raw.m00, raw.m10, raw.m01, raw.m20, raw.m11, raw.m02, raw.m30, raw.m21, raw.m12,
+                raw.m03,
+            ]);
+            let central = raw.central();
+            central_moments.extend_from_slice(&[
+                central.mu20,
+                central.mu11,
+                central.mu02,
+                central.mu30,
+                central.mu21,
+                central.mu12,
+                central.mu03,
+            ]);
+            hu_moments.extend_from_slice(&central.hu(raw.m00));
+            let ellipse = central.ellipse(raw.m00);
+            ellipses.extend_from_slice(&[
+                ellipse.major_axis_length as f32 / scale_down,
+                ellipse.minor_axis_length as f32 / scale_down,
+                ellipse.orientation as f32,
+                ellipse.eccentricity as f32,
+            ]);
+        }
+        self.raw_moments = Some(raw_moments);
+        self.central_moments = Some(central_moments);
+        self.hu_moments = Some(hu_moments);
+        self.ellipses = Some(ellipses);
+    }
+
+    fn store_shape_metrics(
+        &mut self,
+        properties: &[region_labelling::properties::RegionProperties],
+        cracks: &region_labelling::ContourHierarchy,
+        scale_down: f32,
+    ) {
+        let outers = cracks
+            .contours
+            .iter()
+            .zip(&cracks.nodes)
+            .filter(|(_, node)| !node.is_hole);
+        let mut shape_metrics = Vec::with_capacity(properties.len() * 4);
+        for (region, (outer, _)) in properties.iter().zip(outers) {
+            let metrics = region_labelling::shape::shape_metrics(region, outer);
+            shape_metrics.extend_from_slice(&[
+                metrics.polsby_popper as f32,
+                metrics.hull_area as f32 / (scale_down * scale_down),
+                metrics.solidity as f32,
+                metrics.convexity as f32,
+            ]);
+        }
+        self.shape_metrics = Some(shape_metrics);
+    }
+
+    fn store_adjacency_graph(
+        &mut self,
+        edges: &[region_labelling::adjacency::AdjacencyEdge],
+        scale_down: f32,
+    ) {
+        let mut adjacency_edges = Vec::with_capacity(edges.len() * 2);
+        let mut adjacency_weights = Vec::with_capacity(edges.len() * 2);
+        for edge in edges {
+            adjacency_edges.push(edge.a - 1);
+            adjacency_edges.push(edge.b - 1);
+            adjacency_weights.push(edge.shared_length as f32 / scale_down);
+            adjacency_weights.push(edge.separation as f32 / scale_down);
+        }
+        self.adjacency_edges = Some(adjacency_edges);
+        self.adjacency_weights = Some(adjacency_weights);
+    }
+
+    fn store_chain_codes(
+        &mut self,
+        hierarchy: &region_labelling::ContourHierarchy,
+        connectivity: Connectivity,
+    ) {
+        let mut chain_starts = Vec::new();
+        let mut chain_indexes = Vec::new();
+        let mut chain_codes = Vec::new();
+        let borders_then_holes = [false, true].iter().flat_map(|is_hole| {
+            hierarchy
+                .contours
+                .iter()
+                .zip(&hierarchy.nodes)
+                .filter(move |(_, node)| node.is_hole == *is_hole)
+        });
+        for (contour, _) in borders_then_holes {
+            let chain = region_labelling::chain_code::encode(contour, connectivity.into());
+            chain_starts.push(chain.start.x);
+            chain_starts.push(chain.start.y);
+            chain_codes.extend(chain.codes);
+            chain_indexes.push(chain_codes.len());
+        }
+        self.chain_starts = Some(chain_starts);
+        self.chain_indexes = Some(chain_indexes);
+        self.chain_codes = Some(chain_codes);
+        self.chain_connectivity = connectivity;
+    }
+
+    pub fn border_indexes(&self) -> Result<Vec<usize>, String> {
+        match &self.border_indexes {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no border points"),
+        }
+    }
+
+    pub fn border_points(&self) -> Result<Vec<f32>, String> {
+        match &self.border_points {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no border points"),
+        }
+    }
+
+    pub fn hole_indexes(&self) -> Result<Vec<usize>, String> {
+        match &self.hole_indexes {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no hole indexes"),
+        }
+    }
+
+    pub fn hole_points(&self) -> Result<Vec<f32>, String> {
+        match &self.hole_points {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no hole points"),
+        }
+    }
+
+    pub fn hole_parents(&self) -> Result<Vec<usize>, String> {
+        match &self.hole_parents {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no hole parents"),
+        }
+    }
+
+    pub fn hierarchy(&self) -> Result<Vec<i32>, String> {
+        match &self.hierarchy {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no hierarchy"),
+        }
+    }
+
+    pub fn areas(&self) -> Result<Vec<f32>, String> {
+        match &self.areas {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no areas"),
+        }
+    }
+
+    pub fn perimeters(&self) -> Result<Vec<f32>, String> {
+        match &self.perimeters {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no perimeters"),
+        }
+    }
+
+    pub fn centroids(&self) -> Result<Vec<f32>, String> {
+        match &self.centroids {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no centroids"),
+        }
+    }
+
+    pub fn bounding_boxes(&self) -> Result<Vec<f32>, String> {
+        match &self.bounding_boxes {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no bounding boxes"),
+        }
+    }
+
+    pub fn raw_moments(&self) -> Result<Vec<f64>, String> {
+        match &self.raw_moments {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no raw moments"),
+        }
+    }
+
+    pub fn central_moments(&self) -> Result<Vec<f64>, String> {
+        match &self.central_moments {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no central moments"),
+        }
+    }
+
+    pub fn hu_moments(&self) -> Result<Vec<f64>, String> {
+        match &self.hu_moments {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no hu moments"),
+        }
+    }
+
+    pub fn ellipses(&self) -> Result<Vec<f32>, String> {
+        match &self.ellipses {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no ellipses"),
+        }
+    }
+
+    pub fn shape_metrics(&self) -> Result<Vec<f32>, String> {
+        match &self.shape_metrics {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no shape metrics"),
+        }
+    }
+
+    pub fn touches_edge(&self) -> Result<Vec<u8>, String> {
+        match &self.touches_edge {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no touches edge flags"),
+        }
+    }
+
+    pub fn border_classes(&self) -> Result<Vec<u8>, String> {
+        match &self.border_classes {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no border classes"),
+        }
+    }
+
+    pub fn adjacency_edges(&self) -> Result<Vec<u32>, String> {
+        match &self.adjacency_edges {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no adjacency edges"),
+        }
+    }
+
+    pub fn adjacency_weights(&self) -> Result<Vec<f32>, String> {
+        match &self.adjacency_weights {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no adjacency weights"),
+        }
+    }
+
+    pub fn colours(&self) -> Result<Vec<u8>, String> {
+        match &self.colours {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no colours"),
+        }
+    }
+
+    pub fn ids(&self) -> Result<Vec<u32>, String> {
+        match &self.ids {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no ids"),
+        }
+    }
+
+    pub fn chain_starts(&self) -> Result<Vec<u32>, String> {
+        match &self.chain_starts {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no chain starts"),
+        }
+    }
+
+    pub fn chain_indexes(&self) -> Result<Vec<usize>, String> {
+        match &self.chain_indexes {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no chain indexes"),
+        }
+    }
+
+    pub fn chain_codes(&self) -> Result<Vec<u8>, String> {
+        match &self.chain_codes {
+            Some(vec) => Ok(vec.clone()),
+            None => panic!("no chain codes"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate wasm_bindgen_test;
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_when_only_background_pixels_provided() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(0, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(Ok(vec![]), border_points);
         let border_indexes = biscuit_finder.border_indexes();
-        assert_eq!(Ok(vec![2, 4, 6, 8]), border_indexes);
+        assert_eq!(Ok(vec![]), border_indexes);
     }
 
     #[wasm_bindgen_test]
-    fn test_with_multiple_multi_pixel_biscuits() {
+    fn test_with_single_pixel_biscuit_in_top_left_corner() {
         let mut biscuit_finder = BiscuitFinder::new();
 
         let image = rgba_image!(
-            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
-            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
-            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
-            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
-            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+            [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255]);
 
         let input = Clamped(image.to_vec());
-        let result = biscuit_finder.find_biscuits(5, 5, input, 0.0, 0.0, 1.0);
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
 
         assert_eq!(Ok("processed image".into()), result);
 
-        assert_eq!(4, biscuit_finder.num_borders());
+        assert_eq!(1, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(Ok(vec![0.0, 0.0]), border_points);
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![2]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_big_biscuit_in_middle() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 4, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
         let border_points = biscuit_finder.border_points();
         assert_eq!(
-            Ok(vec![
-                0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, //
-                3.0, 0.0, 4.0, 0.0, 4.0, 1.0, 3.0, 1.0, //
-                0.0, 3.0, 1.0, 3.0, 1.0, 4.0, 0.0, 4.0, //
-                3.0, 3.0, 4.0, 3.0, 4.0, 4.0, 3.0, 4.0, //
-            ]),
+            Ok(vec![1.0, 1.0, 2.0, 1.0, 2.0, 2.0, 1.0, 2.0]),
             border_points
         );
         let border_indexes = biscuit_finder.border_indexes();
-        assert_eq!(Ok(vec![8, 16, 24, 32]), border_indexes);
+        assert_eq!(Ok(vec![8]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_multiple_single_pixel_biscuits() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 5, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(4, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![
+                1.0, 1.0, //
+                3.0, 1.0, //
+                1.0, 3.0, //
+                3.0, 3.0, //
+            ]),
+            border_points
+        );
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![2, 4, 6, 8]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_multiple_multi_pixel_biscuits() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 5, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(4, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![
+                0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, //
+                3.0, 0.0, 4.0, 0.0, 4.0, 1.0, 3.0, 1.0, //
+                0.0, 3.0, 1.0, 3.0, 1.0, 4.0, 0.0, 4.0, //
+                3.0, 3.0, 4.0, 3.0, 4.0, 4.0, 3.0, 4.0, //
+            ]),
+            border_points
+        );
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![8, 16, 24, 32]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_biscuit_with_hole() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        assert_eq!(1, biscuit_finder.num_holes());
+        let hole_points = biscuit_finder.hole_points();
+        assert_eq!(
+            Ok(vec![
+                0.0, 1.0, 0.0, 2.0, 1.0, 2.0, 2.0, 1.0, 2.0, 0.0, 1.0, 0.0
+            ]),
+            hole_points
+        );
+        let hole_indexes = biscuit_finder.hole_indexes();
+        assert_eq!(Ok(vec![12]), hole_indexes);
+        let hole_parents = biscuit_finder.hole_parents();
+        assert_eq!(Ok(vec![0]), hole_parents);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_biscuit_inside_hole_of_another() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 5, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(2, biscuit_finder.num_borders());
+        assert_eq!(1, biscuit_finder.num_holes());
+        let hole_parents = biscuit_finder.hole_parents();
+        assert_eq!(Ok(vec![0]), hole_parents);
+        let hierarchy = biscuit_finder.hierarchy();
+        assert_eq!(
+            Ok(vec![
+                -1, -1, 2, -1, //
+                -1, -1, -1, 2, //
+                -1, -1, 1, 0, //
+            ]),
+            hierarchy
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_crack_tracing_of_single_pixel_biscuit() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 10.0, 20.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![10.0, 20.0, 10.5, 20.0, 10.5, 20.5, 10.0, 20.5]),
+            border_points
+        );
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![8]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_marching_squares_tracing_of_single_pixel_biscuit() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::MarchingSquares);
+        biscuit_finder.set_binarisation(Binarisation::Threshold, 64.0);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [128, 128, 128, 255];
+            [128, 128, 128, 255], [128, 128, 128, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![0.5, 0.0, 1.0, 0.5, 0.5, 1.0, 0.0, 0.5]),
+            border_points
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_pixel_walk_tracing_keeps_revisited_points() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::PixelWalk);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0]),
+            border_points
+        );
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![8]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_eight_connectivity_joins_diagonal_pixels() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_connectivity(Connectivity::Eight);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(Ok(vec![0.0, 0.0, 1.0, 1.0]), border_points);
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![4]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_simplification_tolerance_in_device_pixels() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_simplification_tolerance(0.5, Units::DevicePixels);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 3, input, 0.0, 0.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]),
+            border_points
+        );
+        let border_indexes = biscuit_finder.border_indexes();
+        assert_eq!(Ok(vec![8]), border_indexes);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_shared_arc_simplification_of_diagonal_neighbours() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        biscuit_finder.set_simplification_tolerance(0.5, Units::DevicePixels);
+        biscuit_finder.set_simplification_mode(SimplificationMode::SharedArcs);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(2, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![
+                0.0, 0.0, 3.0, 0.0, 3.0, 2.0, 2.0, 2.0, 2.0, 3.0, 0.0, 3.0, //
+                3.0, 2.0, 3.0, 3.0, 2.0, 3.0, 2.0, 2.0, //
+            ]),
+            border_points
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_shared_arc_simplification_across_street() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        biscuit_finder.set_simplification_tolerance(1.0, Units::DevicePixels);
+        biscuit_finder.set_simplification_mode(SimplificationMode::SharedArcs);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(6, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(2, biscuit_finder.num_borders());
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![
+                0.0, 0.0, 3.0, 0.0, 4.0, 3.0, 0.0, 3.0, //
+                3.0, 0.0, 6.0, 0.0, 6.0, 3.0, 4.0, 3.0, //
+            ]),
+            border_points
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_shared_arc_simplification_and_chain_coding() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        biscuit_finder.set_simplification_mode(SimplificationMode::SharedArcs);
+        biscuit_finder.set_chain_coding(true);
+
+        let image = rgba_image!(
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 4, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(2, biscuit_finder.num_borders());
+        assert_eq!(0, biscuit_finder.num_holes());
+        assert_eq!(2, biscuit_finder.num_chains());
+        assert_eq!(Ok(vec![2, 0, 0, 0]), biscuit_finder.chain_starts());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_shared_arc_simplification_tiling_the_image() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        biscuit_finder.set_simplification_tolerance(3.0, Units::DevicePixels);
+        biscuit_finder.set_simplification_mode(SimplificationMode::SharedArcs);
+
+        let rings = |indexes: Vec<usize>, points: Vec<f32>| {
+            let mut start = 0;
+            let mut rings = Vec::new();
+            for end in indexes {
+                let ring: Vec<(f32, f32)> = points[start..end]
+                    .chunks(2)
+                    .map(|point| (point[0], point[1]))
+                    .collect();
+                rings.push(ring);
+                start = end;
+            }
+            rings
+        };
+        let mut seed = 12345u32;
+        for (width, height) in [(10, 3), (12, 9)].iter().cycle().take(40) {
+            let image = image::RgbaImage::from_fn(*width, *height, |_, _| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                if (seed >> 16) & 3 == 0 {
+                    Rgba([255, 255, 255, 255])
+                } else {
+                    Rgba([0, 0, 0, 255])
+                }
+            });
+
+            let input = Clamped(image.to_vec());
+            let result = biscuit_finder.find_biscuits(*width, *height, input, 0.0, 0.0, 1.0);
+
+            assert_eq!(Ok("processed image".into()), result);
+
+            let borders = rings(
+                biscuit_finder.border_indexes().unwrap(),
+                biscuit_finder.border_points().unwrap(),
+            );
+            let holes = rings(
+                biscuit_finder.hole_indexes().unwrap(),
+                biscuit_finder.hole_points().unwrap(),
+            );
+            let mut total_area = 0.0;
+            for ring in &borders {
+                let area = geometry::signed_area_doubled(ring).abs() / 2.0;
+                assert!(ring.len() >= 3 && area > 0.0, "{:?}", ring);
+                total_area += area;
+            }
+            for ring in &holes {
+                let area = geometry::signed_area_doubled(ring).abs() / 2.0;
+                assert!(ring.len() >= 3 && area > 0.0, "{:?}", ring);
+                total_area -= area;
+            }
+            if !borders.is_empty() {
+                assert_eq!((width * height) as f32, total_area);
+            }
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_chain_coding() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_chain_coding(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(2, biscuit_finder.num_chains());
+        assert_eq!(Ok(vec![0, 0, 2, 1]), biscuit_finder.chain_starts());
+        assert_eq!(Ok(vec![4, 4]), biscuit_finder.chain_indexes());
+        assert_eq!(Ok(vec![0, 2, 3, 1]), biscuit_finder.chain_codes());
+        assert_eq!(vec![0, 0, 1, 0, 0, 0, 0, 1], biscuit_finder.decode_chain(0));
+        assert_eq!(vec![2, 1], biscuit_finder.decode_chain(1));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_normalised_rings() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        biscuit_finder.set_normalise_rings(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        let border_points = biscuit_finder.border_points();
+        assert_eq!(
+            Ok(vec![0.0, 0.0, 3.0, 0.0, 3.0, 3.0, 0.0, 3.0, 0.0, 0.0]),
+            border_points
+        );
+        let hole_points = biscuit_finder.hole_points();
+        assert_eq!(
+            Ok(vec![1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0]),
+            hole_points
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_region_properties() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 10.0, 20.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(2, biscuit_finder.num_biscuits());
+        assert_eq!(Ok(vec![1.0, 0.25]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![4.0, 2.0]), biscuit_finder.perimeters());
+        assert_eq!(
+            Ok(vec![10.25, 20.25, 11.5, 20.5]),
+            biscuit_finder.centroids()
+        );
+        assert_eq!(
+            Ok(vec![
+                9.75, 19.75, 10.75, 20.75, //
+                11.25, 20.25, 11.75, 20.75, //
+            ]),
+            biscuit_finder.bounding_boxes()
+        );
+        assert_eq!(
+            Ok(vec![
+                10.0, 20.0, 10.5, 20.0, 10.5, 20.5, 10.0, 20.5, //
+                11.5, 20.5, //
+            ]),
+            biscuit_finder.border_points()
+        );
+
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 10.0, 20.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(
+            Ok(vec![10.5, 20.5, 11.75, 20.75]),
+            biscuit_finder.centroids()
+        );
+        assert_eq!(
+            Ok(vec![
+                10.0, 20.0, 11.0, 21.0, //
+                11.5, 20.5, 12.0, 21.0, //
+            ]),
+            biscuit_finder.bounding_boxes()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_moments_of_vertical_bar() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_shape_analysis(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 3, input, 0.0, 0.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(
+            Ok(vec![3.0, 0.0, 3.0, 0.0, 0.0, 5.0, 0.0, 0.0, 0.0, 9.0]),
+            biscuit_finder.raw_moments()
+        );
+        assert_eq!(
+            Ok(vec![0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0]),
+            biscuit_finder.central_moments()
+        );
+        let ellipses = biscuit_finder.ellipses().unwrap();
+        assert!((ellipses[0] - 2.0 * (2.0f32 / 3.0).sqrt()).abs() < 1e-6);
+        assert_eq!(0.0, ellipses[1]);
+        assert!((ellipses[2] - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(1.0, ellipses[3]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_shape_metrics_of_l_shape() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_shape_analysis(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        let shape_metrics = biscuit_finder.shape_metrics().unwrap();
+        assert_eq!(4, shape_metrics.len());
+        let perimeter = 2.0 + 3.0 * std::f32::consts::SQRT_2;
+        let polsby_popper = 10.0 * std::f32::consts::PI / (perimeter * perimeter);
+        assert!((shape_metrics[0] - polsby_popper).abs() < 1e-6);
+        assert_eq!(3.5, shape_metrics[1]);
+        assert_eq!(3.0 / 3.5, shape_metrics[2]);
+
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_shape_analysis(true);
+        biscuit_finder.set_contour_tracing(ContourTracing::Cracks);
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 2, input, 0.0, 0.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+        assert_eq!(Ok(shape_metrics), biscuit_finder.shape_metrics());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_area_range_drops_specks() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_area_range(2.0, f32::INFINITY, Units::DevicePixels);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        assert_eq!(Ok(vec![2.0, 0.0, 2.0, 1.0]), biscuit_finder.border_points());
+        assert_eq!(Ok(vec![2.0]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_area_range_in_output_units() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_area_range(0.0, 0.3, Units::Output);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 2, input, 0.0, 0.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        assert_eq!(Ok(vec![0.0, 0.0]), biscuit_finder.border_points());
+        assert_eq!(Ok(vec![0.25]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_edge_biscuits_flagged_and_dropped() {
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+
+        let mut biscuit_finder = BiscuitFinder::new();
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+        assert_eq!(Ok(vec![1, 0]), biscuit_finder.touches_edge());
+
+        biscuit_finder.set_drop_edge_biscuits(true);
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+        assert_eq!(Ok(vec![2.0, 1.0]), biscuit_finder.border_points());
+        assert_eq!(Ok(vec![0]), biscuit_finder.touches_edge());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_closing_fills_gap_in_street() {
+        let image = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let mut biscuit_finder = BiscuitFinder::new();
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+        assert_eq!(1, biscuit_finder.num_borders());
+
+        biscuit_finder.set_morphology(Morphology::Close, StructuringElement::Square, 1);
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+        assert_eq!(2, biscuit_finder.num_borders());
+        assert_eq!(Ok(vec![6.0, 6.0]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_merging_sliver_across_stray_line() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_merge_below_area(3.0, 1.0, Units::DevicePixels);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+        assert_eq!(Ok(vec![8.0]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_merging_sliver_across_high_dpi_line() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_merge_below_area(1.0, 1.0, Units::Output);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 2, input, 0.0, 0.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_borders());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_adjacency_graph_across_street() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_adjacency_graph(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 0.0, 0.0, 2.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(1, biscuit_finder.num_adjacency_edges());
+        assert_eq!(Ok(vec![0, 1]), biscuit_finder.adjacency_edges());
+        assert_eq!(Ok(vec![1.0, 1.0]), biscuit_finder.adjacency_weights());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_colouring_of_blocks_either_side_of_streets() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_colouring(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        let colours = biscuit_finder.colours().unwrap();
+        assert_eq!(4, colours.len());
+        assert_ne!(colours[0], colours[1]);
+        assert_ne!(colours[0], colours[2]);
+        assert_ne!(colours[1], colours[3]);
+        assert_ne!(colours[2], colours[3]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_ids_unique_for_island_in_courtyard() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let image = image::RgbaImage::from_fn(7, 7, |x, y| {
+            let on_ring = (1..=5).contains(&x)
+                && (1..=5).contains(&y)
+                && (x == 1 || x == 5 || y == 1 || y == 5);
+            if on_ring || (x, y) == (3, 3) {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        });
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(7, 7, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![3.0, 3.0, 3.0, 3.0]), biscuit_finder.centroids());
+        let ids = biscuit_finder.ids().unwrap();
+        assert_eq!(2, ids.len());
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(geometry::stable_id(3.0, 3.0, 1.0), ids[0]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_ids_stable_when_panning() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_id_precision(0.5);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 0.0, 0.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+        let before = biscuit_finder.ids().unwrap();
+
+        let panned = rgba_image!(
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_id_precision(0.5);
+        let input = Clamped(panned.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 1.0, -1.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+        let after = biscuit_finder.ids().unwrap();
+
+        assert_eq!(2, before.len());
+        assert_ne!(before[0], before[1]);
+        assert_eq!(vec![before[1]], after);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_background_colour_and_tolerance() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_background_colour(40, 40, 60, 255, 10.0, ColourDistance::PerChannel);
+
+        let image = rgba_image!(
+            [200, 180, 160, 255], [40,   40,  60, 255], [200, 180, 160, 255];
+            [45,   35,  65, 255], [50,   40,  60, 255], [40,   40,  71, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 2.0]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_perceptual_tolerance_absorbing_antialiasing() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_background_colour(255, 255, 255, 255, 2.3, ColourDistance::Perceptual);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [250, 250, 250, 255], [0,     0,   0, 255];
+            [253, 253, 253, 255], [255, 255, 255, 255], [240, 240, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 2.0]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_fixed_luminance_threshold() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_binarisation(Binarisation::Threshold, 128.0);
+
+        let image = rgba_image!(
+            [100, 100, 100, 255], [200, 200, 200, 255], [128, 128, 128, 255];
+            [230, 230, 230, 255], [127, 127, 127, 255], [ 20,  20,  20, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 2.0]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![0.0, 0.0, 1.5, 1.0]), biscuit_finder.centroids());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_otsu_threshold_between_grey_streets_and_dark_blocks() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_binarisation(Binarisation::Otsu, 0.0);
+
+        let image = rgba_image!(
+            [ 40,  40,  40, 255], [170, 170, 170, 255], [ 60,  60,  60, 255];
+            [180, 180, 180, 255], [160, 160, 160, 255], [175, 175, 175, 255];
+            [ 50,  50,  50, 255], [165, 165, 165, 255], [ 45,  45,  45, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(3, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 1.0, 1.0, 1.0]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_palette_reporting_border_classes() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_binarisation(Binarisation::Palette, 0.0);
+        biscuit_finder.add_palette_colour(255, 255, 255, 255, PixelClass::Barrier);
+        biscuit_finder.add_palette_colour(0, 0, 255, 255, PixelClass::Water);
+        biscuit_finder.add_palette_colour(255, 0, 0, 255, PixelClass::Ignore);
+
+        let image = rgba_image!(
+            [0,   128,   0, 255], [255, 255, 255, 255], [0,   128,   0, 255], [0,     0, 255, 255];
+            [255,   0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(4, 2, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 1.0]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![0b1001, 0b0011]), biscuit_finder.border_classes());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_alpha_cutoff_outside_clip() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_alpha_cutoff(128);
+        biscuit_finder.set_adjacency_graph(true);
+
+        let image = rgba_image!(
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0,   0];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0,   0];
+            [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255], [12,  34,  56,  10], [0,     0,   0, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 1.0, 1.0, 1.0]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![1, 1, 1, 1]), biscuit_finder.touches_edge());
+        assert_eq!(
+            Ok(vec![0b0001, 0b0001, 0b0001, 0b10000]),
+            biscuit_finder.border_classes()
+        );
+        assert_eq!(Ok(vec![0, 1, 0, 2, 1, 2]), biscuit_finder.adjacency_edges());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_ignored_label_between_interior_blocks() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_binarisation(Binarisation::Palette, 0.0);
+        biscuit_finder.add_palette_colour(255, 255, 255, 255, PixelClass::Barrier);
+        biscuit_finder.add_palette_colour(255, 0, 0, 255, PixelClass::Ignore);
+        biscuit_finder.set_alpha_cutoff(128);
+        biscuit_finder.set_drop_edge_biscuits(true);
+        biscuit_finder.set_adjacency_graph(true);
+
+        let image = rgba_image!(
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [0,     0,   0, 255], [255,   0,   0, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(5, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 1.0]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![0, 0]), biscuit_finder.touches_edge());
+        assert_eq!(Ok(vec![0b1001, 0b1001]), biscuit_finder.border_classes());
+        assert_eq!(Ok(vec![0, 1]), biscuit_finder.adjacency_edges());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_in_region_of_larger_buffer() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let canvas = rgba_image!(
+            [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255], [0,     0,   0, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [0,     0,   0, 255], [255, 255, 255, 255];
+            [0,     0,   0, 255], [255, 255, 255, 255], [255, 255, 255, 255], [0,     0,   0, 255]);
+
+        biscuit_finder.resize_input(canvas.len());
+        biscuit_finder.input.copy_from_slice(&canvas);
+        let result = biscuit_finder.find_biscuits_in_region(16, 1, 1, 3, 2, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 1.0]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![1.0, 0.0, 2.0, 1.0]), biscuit_finder.centroids());
+
+        let result = biscuit_finder.find_biscuits_in_region(16, 2, 1, 2, 2, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(Ok(vec![1.0, 1.0]), biscuit_finder.areas());
+        assert_eq!(Ok(vec![0.0, 0.0, 1.0, 1.0]), biscuit_finder.centroids());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_empty_image() {
+        let mut biscuit_finder = BiscuitFinder::new();
+
+        let input = Clamped(vec![]);
+        let result = biscuit_finder.find_biscuits(0, 3, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(0, biscuit_finder.num_borders());
+        assert_eq!(Ok(vec![]), biscuit_finder.areas());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_otsu_threshold_for_marching_squares() {
+        let image = rgba_image!(
+            [ 40,  40,  40, 255], [170, 170, 170, 255], [ 60,  60,  60, 255];
+            [180, 180, 180, 255], [160, 160, 160, 255], [175, 175, 175, 255];
+            [ 50,  50,  50, 255], [165, 165, 165, 255], [ 45,  45,  45, 255]);
+
+        let mut otsu = BiscuitFinder::new();
+        otsu.set_contour_tracing(ContourTracing::MarchingSquares);
+        otsu.set_binarisation(Binarisation::Otsu, 0.0);
+        let result = otsu.find_biscuits(3, 3, Clamped(image.to_vec()), 0.0, 0.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+
+        let mut fixed = BiscuitFinder::new();
+        fixed.set_contour_tracing(ContourTracing::MarchingSquares);
+        fixed.set_binarisation(Binarisation::Threshold, 60.5);
+        let result = fixed.find_biscuits(3, 3, Clamped(image.to_vec()), 0.0, 0.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(4, otsu.num_borders());
+        assert_eq!(fixed.border_points(), otsu.border_points());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_otsu_threshold_ignoring_transparent_margin() {
+        let content = rgba_image!(
+            [150, 150, 150, 255], [240, 240, 240, 255], [160, 160, 160, 255], [255, 255, 255, 255];
+            [245, 245, 245, 255], [235, 235, 235, 255], [250, 250, 250, 255], [255, 255, 255, 255];
+            [155, 155, 155, 255], [238, 238, 238, 255], [152, 152, 152, 255], [255, 255, 255, 255];
+            [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255], [255, 255, 255, 255]);
+        let padded = image::RgbaImage::from_fn(10, 10, |x, y| {
+            if x < 4 && y < 4 {
+                *content.get_pixel(x, y)
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+
+        let mut unpadded = BiscuitFinder::new();
+        unpadded.set_contour_tracing(ContourTracing::MarchingSquares);
+        unpadded.set_binarisation(Binarisation::Otsu, 0.0);
+        let result = unpadded.find_biscuits(4, 4, Clamped(content.to_vec()), 0.0, 0.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::MarchingSquares);
+        biscuit_finder.set_binarisation(Binarisation::Otsu, 0.0);
+        biscuit_finder.set_alpha_cutoff(128);
+        let result = biscuit_finder.find_biscuits(10, 10, Clamped(padded.to_vec()), 0.0, 0.0, 1.0);
+        assert_eq!(Ok("processed image".into()), result);
+
+        assert_eq!(4, biscuit_finder.num_borders());
+        assert_eq!(unpadded.border_points(), biscuit_finder.border_points());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_with_marching_squares_towards_transparent_pixel() {
+        let mut biscuit_finder = BiscuitFinder::new();
+        biscuit_finder.set_contour_tracing(ContourTracing::MarchingSquares);
+        biscuit_finder.set_binarisation(Binarisation::Threshold, 128.0);
+        biscuit_finder.set_alpha_cutoff(128);
+
+        let image = rgba_image!([100, 100, 100, 255], [0, 0, 0, 0]);
+
+        let input = Clamped(image.to_vec());
+        let result = biscuit_finder.find_biscuits(2, 1, input, 0.0, 0.0, 1.0);
+
+        assert_eq!(Ok("processed image".into()), result);
+
+        let crossing = 0.5 + (128.0 - 100.0) / (255.0 - 100.0);
+        assert_eq!(
+            Ok(vec![0.5, 0.0, crossing, 0.5, 0.5, 1.0, 0.0, 0.5]),
+            biscuit_finder.border_points()
+        );
     }
 }
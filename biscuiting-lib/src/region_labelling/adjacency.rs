@@ -0,0 +1,450 @@
+use super::is_in_bounds;
+use super::properties::region_properties;
+use bit_set::BitSet;
+use image::Luma;
+use imageproc::definitions::Image;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+const NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// An edge of the region adjacency graph, between the regions labelled `a` and
+/// `b`, with `a < b`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AdjacencyEdge {
+    pub a: u32,
+    pub b: u32,
+    /// Number of pixel edges where the areas closest to each region meet.
+    pub shared_length: u32,
+    /// Fewest background pixels crossed to get from one region to the other.
+    pub separation: u32,
+}
+
+/// The region each pixel is fewest steps from, and how many steps that is,
+/// found by a breadth-first search out from every region at once. Pixels for
+/// which `is_outside` is true belong to no region and cannot be crossed.
+fn nearest_regions<F>(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    is_outside: F,
+) -> Vec<Option<(u32, u32)>>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let width = image.width();
+    let mut owners: Vec<Option<(u32, u32)>> = vec![None; (width * image.height()) as usize];
+    let mut pending = VecDeque::new();
+    for (x, y, color) in image.enumerate_pixels() {
+        if *color != background_color {
+            owners[(y * width + x) as usize] = Some((color[0], 0));
+            pending.push_back((x, y));
+        }
+    }
+    while let Some((x, y)) = pending.pop_front() {
+        let (label, distance) = owners[(y * width + x) as usize].unwrap();
+        for (dx, dy) in &NEIGHBOURS {
+            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+            if is_in_bounds(nx, ny, image) && !is_outside(nx as u32, ny as u32) {
+                let index = (ny as u32 * width + nx as u32) as usize;
+                if owners[index].is_none() {
+                    owners[index] = Some((label, distance + 1));
+                    pending.push_back((nx as u32, ny as u32));
+                }
+            }
+        }
+    }
+    owners
+}
+
+/// Builds the region adjacency graph of a labelled image. Every background
+/// pixel is given to the region it is fewest steps from, so two regions are
+/// neighbours when the areas closest to them touch, such as blocks on either
+/// side of a street. Pixels for which `is_outside` is true belong to no region
+/// and cannot be crossed. Edges are in order of `a`, then `b`.
+pub fn region_adjacency_graph<F>(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    is_outside: F,
+) -> Vec<AdjacencyEdge>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let owners = nearest_regions(background_color, image, is_outside);
+    adjacency_edges(image.width(), image.height(), &owners, u32::MAX)
+}
+
+/// Grows every region of a labelled image into the background pixels it is
+/// fewest steps from, as in `region_adjacency_graph`, so that the regions tile
+/// the image and regions on either side of a street meet along its middle.
+/// Pixels for which `is_outside` is true stay `background_color`.
+pub fn nearest_region_labels<F>(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    is_outside: F,
+) -> Image<Luma<u32>>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let owners = nearest_regions(background_color, image, is_outside);
+    let width = image.width();
+    Image::from_fn(width, image.height(), |x, y| {
+        match owners[(y * width + x) as usize] {
+            Some((label, _)) => Luma([label]),
+            None => background_color,
+        }
+    })
+}
+
+/// The edges between the areas in `owners` closest to each region, counting
+/// only the pixel edges at most `max_separation` background pixels from both.
+fn adjacency_edges(
+    width: u32,
+    height: u32,
+    owners: &[Option<(u32, u32)>],
+    max_separation: u32,
+) -> Vec<AdjacencyEdge> {
+    let mut edges: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            let owner = owners[(y * width + x) as usize];
+            let neighbours = [(x + 1, y), (x, y + 1)];
+            for (nx, ny) in neighbours.iter().filter(|(x, y)| *x < width && *y < height) {
+                let other = owners[(ny * width + nx) as usize];
+                if let (Some((a, da)), Some((b, db))) = (owner, other) {
+                    if a != b && da + db <= max_separation {
+                        let edge = edges.entry((a.min(b), a.max(b))).or_insert((0, u32::MAX));
+                        edge.0 += 1;
+                        edge.1 = edge.1.min(da + db);
+                    }
+                }
+            }
+        }
+    }
+    let mut edges: Vec<AdjacencyEdge> = edges
+        .into_iter()
+        .map(|((a, b), (shared_length, separation))| AdjacencyEdge {
+            a,
+            b,
+            shared_length,
+            separation,
+        })
+        .collect();
+    edges.sort_unstable_by_key(|edge| (edge.a, edge.b));
+    edges
+}
+
+/// Merges every region smaller than `min_area` pixels into the neighbouring
+/// region it shares the longest frontage with in the region adjacency graph,
+/// counting only frontage at most `max_gap` background pixels across, and fills
+/// the gap between them. Only regions of at least `min_area` take in others, so
+/// small regions never pile up into one another. Regions are then renumbered
+/// from 1 in raster order; those with no such neighbour are left alone.
+pub fn merge_small_regions<F>(
+    background_color: Luma<u32>,
+    image: &mut Image<Luma<u32>>,
+    min_area: u32,
+    max_gap: u32,
+    is_outside: F,
+) where
+    F: Fn(u32, u32) -> bool,
+{
+    let properties = region_properties(background_color, image);
+    if properties.iter().all(|region| region.area >= min_area) {
+        return;
+    }
+    let (width, height) = image.dimensions();
+    let owners = nearest_regions(background_color, image, is_outside);
+    let is_small = |label: u32| properties[label as usize - 1].area < min_area;
+    let mut longest: HashMap<u32, (u32, u32)> = HashMap::new();
+    for edge in adjacency_edges(width, height, &owners, max_gap) {
+        for (label, other) in &[(edge.a, edge.b), (edge.b, edge.a)] {
+            if !is_small(*label) || is_small(*other) {
+                continue;
+            }
+            let best = longest
+                .entry(*label)
+                .or_insert((*other, edge.shared_length));
+            if edge.shared_length > best.1 || (edge.shared_length == best.1 && *other < best.0) {
+                *best = (*other, edge.shared_length);
+            }
+        }
+    }
+    let roots: Vec<u32> = (1..=properties.len() as u32)
+        .map(|label| longest.get(&label).map_or(label, |(other, _)| *other))
+        .collect();
+    let root_of = |label: u32| roots[label as usize - 1];
+    for pixel in image.pixels_mut() {
+        if *pixel != background_color {
+            *pixel = Luma([root_of(pixel[0])]);
+        }
+    }
+    for y in 0..height {
+        for x in 0..width {
+            let (label, distance) = match owners[(y * width + x) as usize] {
+                Some(owner) => owner,
+                None => continue,
+            };
+            let bridges = NEIGHBOURS
+                .iter()
+                .map(|(dx, dy)| (x as i32 + dx, y as i32 + dy))
+                .filter(|(x, y)| *x >= 0 && *y >= 0 && *x < width as i32 && *y < height as i32)
+                .filter_map(|(x, y)| owners[(y as u32 * width + x as u32) as usize])
+                .any(|(other, other_distance)| {
+                    other != label
+                        && root_of(other) == root_of(label)
+                        && distance + other_distance <= max_gap
+                });
+            if !bridges {
+                continue;
+            }
+            let (mut x, mut y, mut distance) = (x, y, distance);
+            while distance > 0 {
+                image.put_pixel(x, y, Luma([root_of(label)]));
+                let (nx, ny) = NEIGHBOURS
+                    .iter()
+                    .map(|(dx, dy)| (x as i32 + dx, y as i32 + dy))
+                    .find(|(nx, ny)| {
+                        is_in_bounds(*nx, *ny, image)
+                            && owners[(*ny as u32 * width + *nx as u32) as usize]
+                                == Some((label, distance - 1))
+                    })
+                    .unwrap();
+                x = nx as u32;
+                y = ny as u32;
+                distance -= 1;
+            }
+        }
+    }
+    let mut new_labels: HashMap<u32, u32> = HashMap::new();
+    for pixel in image.pixels_mut() {
+        if *pixel != background_color {
+            let next_label = new_labels.len() as u32 + 1;
+            *pixel = Luma([*new_labels.entry(pixel[0]).or_insert(next_label)]);
+        }
+    }
+}
+
+/// Colours `num_regions` regions with DSATUR so that no two ends of an edge
+/// share a colour: the uncoloured region with the most distinct colours among
+/// its neighbours, then the most neighbours, gets the smallest colour they do
+/// not use. Regions wait in a heap that is topped up whenever a region's
+/// count goes up, with stale entries skipped, so picking one is `O(log n)`.
+/// Index `n - 1` is the colour of the region labelled `n`.
+pub fn colour_regions(num_regions: usize, edges: &[AdjacencyEdge]) -> Vec<u8> {
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); num_regions];
+    for edge in edges {
+        let (a, b) = (edge.a as usize - 1, edge.b as usize - 1);
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+    let mut colours: Vec<Option<u8>> = vec![None; num_regions];
+    let mut neighbour_colours: Vec<BitSet> = vec![BitSet::new(); num_regions];
+    let priority =
+        |region: usize, saturation: usize| (saturation, neighbours[region].len(), Reverse(region));
+    let mut pending: BinaryHeap<(usize, usize, Reverse<usize>)> =
+        (0..num_regions).map(|region| priority(region, 0)).collect();
+    while let Some((saturation, _, Reverse(next))) = pending.pop() {
+        if colours[next].is_some() || saturation != neighbour_colours[next].len() {
+            continue;
+        }
+        let colour = (0..)
+            .find(|colour| !neighbour_colours[next].contains(*colour))
+            .unwrap();
+        colours[next] = Some(colour as u8);
+        for neighbour in &neighbours[next] {
+            if colours[*neighbour].is_none() && neighbour_colours[*neighbour].insert(colour) {
+                pending.push(priority(*neighbour, neighbour_colours[*neighbour].len()));
+            }
+        }
+    }
+    colours.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_small_regions_joins_sliver_to_longest_neighbour() {
+        let mut image = gray_image!(type: u32,
+            1, 0, 2, 2, 2;
+            1, 0, 2, 2, 2;
+            0, 0, 0, 0, 0;
+            3, 3, 3, 3, 3);
+
+        let background_color = Luma([0u32; 1]);
+
+        merge_small_regions(background_color, &mut image, 3, 1, |_, _| false);
+
+        assert_eq!(
+            gray_image!(type: u32,
+                1, 1, 1, 1, 1;
+                1, 1, 1, 1, 1;
+                0, 0, 0, 0, 0;
+                2, 2, 2, 2, 2),
+            image
+        );
+    }
+
+    #[test]
+    fn test_merge_small_regions_only_into_large_regions() {
+        let mut image = gray_image!(type: u32,
+            1, 0, 2, 0, 3, 3, 3;
+            1, 0, 2, 0, 3, 3, 3);
+
+        merge_small_regions(Luma([0u32; 1]), &mut image, 3, 1, |_, _| false);
+
+        assert_eq!(
+            gray_image!(type: u32,
+                1, 0, 2, 2, 2, 2, 2;
+                1, 0, 2, 2, 2, 2, 2),
+            image
+        );
+    }
+
+    #[test]
+    fn test_merge_small_regions_bridges_wider_gap() {
+        let mut image = gray_image!(type: u32,
+            1, 0, 0, 2, 2, 2;
+            1, 0, 0, 2, 2, 2;
+            0, 0, 0, 0, 0, 0;
+            0, 0, 0, 0, 0, 0;
+            0, 0, 0, 0, 0, 0;
+            3, 3, 3, 3, 3, 3);
+
+        let background_color = Luma([0u32; 1]);
+
+        let mut too_narrow = image.clone();
+        merge_small_regions(background_color, &mut too_narrow, 3, 1, |_, _| false);
+        assert_eq!(image, too_narrow);
+
+        merge_small_regions(background_color, &mut image, 3, 2, |_, _| false);
+
+        assert_eq!(
+            gray_image!(type: u32,
+                1, 1, 1, 1, 1, 1;
+                1, 1, 1, 1, 1, 1;
+                0, 0, 0, 0, 0, 0;
+                0, 0, 0, 0, 0, 0;
+                0, 0, 0, 0, 0, 0;
+                2, 2, 2, 2, 2, 2),
+            image
+        );
+    }
+
+    #[test]
+    fn test_nearest_region_labels_meet_across_streets() {
+        let image = gray_image!(type: u32,
+            1, 0, 0, 0, 2;
+            1, 0, 0, 0, 2;
+            0, 0, 0, 0, 0);
+
+        let labels = nearest_region_labels(Luma([0u32; 1]), &image, |x, _| x == 2);
+
+        assert_eq!(
+            gray_image!(type: u32,
+                1, 1, 0, 2, 2;
+                1, 1, 0, 2, 2;
+                1, 1, 0, 2, 2),
+            labels
+        );
+    }
+
+    #[test]
+    fn test_region_adjacency_graph_across_streets() {
+        let image = gray_image!(type: u32,
+            1, 1, 0, 2;
+            1, 1, 0, 2;
+            0, 0, 0, 0;
+            0, 0, 0, 0;
+            3, 3, 3, 3);
+
+        let background_color = Luma([0u32; 1]);
+
+        let edges = region_adjacency_graph(background_color, &image, |_, _| false);
+
+        assert_eq!(
+            vec![
+                AdjacencyEdge {
+                    a: 1,
+                    b: 2,
+                    shared_length: 3,
+                    separation: 1,
+                },
+                AdjacencyEdge {
+                    a: 1,
+                    b: 3,
+                    shared_length: 3,
+                    separation: 2,
+                },
+                AdjacencyEdge {
+                    a: 2,
+                    b: 3,
+                    shared_length: 1,
+                    separation: 2,
+                },
+            ],
+            edges
+        );
+    }
+
+    #[test]
+    fn test_colour_regions_gives_neighbours_different_colours() {
+        let edge = |a, b| AdjacencyEdge {
+            a,
+            b,
+            shared_length: 1,
+            separation: 0,
+        };
+        let edges = vec![
+            edge(1, 2),
+            edge(2, 3),
+            edge(3, 4),
+            edge(4, 5),
+            edge(1, 5),
+            edge(1, 6),
+            edge(2, 6),
+            edge(3, 6),
+            edge(4, 6),
+            edge(5, 6),
+        ];
+
+        let colours = colour_regions(7, &edges);
+
+        for edge in &edges {
+            assert_ne!(colours[edge.a as usize - 1], colours[edge.b as usize - 1]);
+        }
+        assert_eq!(4, colours.iter().max().unwrap() + 1);
+    }
+
+    #[test]
+    fn test_colour_regions_two_colours_a_large_grid() {
+        let side = 100;
+        let edge = |a, b| AdjacencyEdge {
+            a,
+            b,
+            shared_length: 1,
+            separation: 1,
+        };
+        let mut edges = Vec::new();
+        for y in 0..side {
+            for x in 0..side {
+                let label = y * side + x + 1;
+                if x + 1 < side {
+                    edges.push(edge(label, label + 1));
+                }
+                if y + 1 < side {
+                    edges.push(edge(label, label + side));
+                }
+            }
+        }
+
+        let colours = colour_regions((side * side) as usize, &edges);
+
+        for edge in &edges {
+            assert_ne!(colours[edge.a as usize - 1], colours[edge.b as usize - 1]);
+        }
+        assert_eq!(2, colours.iter().max().unwrap() + 1);
+        assert_eq!(0, colours[6]);
+    }
+}
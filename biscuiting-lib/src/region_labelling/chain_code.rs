@@ -0,0 +1,130 @@
+use super::Point;
+use imageproc::region_labelling::Connectivity;
+
+/// Freeman directions, counter-clockwise from east as seen on screen, so north
+/// is towards smaller `y`.
+const FOUR_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, -1), (-1, 0), (0, 1)];
+const EIGHT_DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A closed contour as its first point and the direction of each step after it,
+/// including the final step back to the first point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainCode {
+    pub start: Point<u32>,
+    pub codes: Vec<u8>,
+}
+
+fn directions(connectivity: Connectivity) -> &'static [(i32, i32)] {
+    match connectivity {
+        Connectivity::Four => &FOUR_DIRECTIONS,
+        Connectivity::Eight => &EIGHT_DIRECTIONS,
+    }
+}
+
+/// Encodes a contour whose consecutive points, and last and first points, are
+/// neighbours under `connectivity`, as the pixel walk produces them.
+pub fn encode(contour: &[Point<u32>], connectivity: Connectivity) -> ChainCode {
+    let directions = directions(connectivity);
+    let codes = if contour.len() < 2 {
+        Vec::new()
+    } else {
+        contour
+            .iter()
+            .zip(contour.iter().cycle().skip(1))
+            .map(|(from, to)| {
+                let step = (to.x as i32 - from.x as i32, to.y as i32 - from.y as i32);
+                directions
+                    .iter()
+                    .position(|direction| *direction == step)
+                    .expect("contour points are not neighbours") as u8
+            })
+            .collect()
+    };
+    ChainCode {
+        start: contour[0],
+        codes,
+    }
+}
+
+/// The points of the contour `chain` encodes, without the first point repeated.
+pub fn decode(chain: &ChainCode, connectivity: Connectivity) -> Vec<Point<u32>> {
+    let directions = directions(connectivity);
+    let mut points = vec![chain.start];
+    let (mut x, mut y) = (chain.start.x as i32, chain.start.y as i32);
+    for code in chain.codes.iter().take(chain.codes.len().saturating_sub(1)) {
+        let (dx, dy) = directions[*code as usize];
+        x += dx;
+        y += dy;
+        points.push(Point::new(x as u32, y as u32));
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_labelling::{find_contour_hierarchy_in_luma, Tracing};
+    use image::Luma;
+
+    #[test]
+    fn test_chain_code_of_pixel_walk_round_trips() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0,   0;
+            0, 255, 255, 255, 255;
+            0, 255, 255,   0,   0;
+            0,   0,   0,   0,   0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Four,
+        );
+        let chain = encode(&hierarchy.contours[0], Connectivity::Four);
+
+        assert_eq!(Point::new(1, 1), chain.start);
+        assert_eq!(vec![0, 0, 0, 2, 2, 3, 2, 1], chain.codes);
+        assert_eq!(hierarchy.contours[0], decode(&chain, Connectivity::Four));
+    }
+
+    #[test]
+    fn test_chain_code_with_eight_directions() {
+        let image = gray_image!(type: u32,
+              0, 255,   0;
+            255,   0, 255;
+              0, 255,   0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Eight,
+        );
+        let chain = encode(&hierarchy.contours[0], Connectivity::Eight);
+
+        assert_eq!(Point::new(1, 0), chain.start);
+        assert_eq!(vec![7, 5, 3, 1], chain.codes);
+        assert_eq!(hierarchy.contours[0], decode(&chain, Connectivity::Eight));
+    }
+
+    #[test]
+    fn test_chain_code_of_single_pixel() {
+        let chain = encode(&[Point::new(2, 3)], Connectivity::Four);
+
+        assert!(chain.codes.is_empty());
+        assert_eq!(vec![Point::new(2, 3)], decode(&chain, Connectivity::Four));
+    }
+}
@@ -0,0 +1,103 @@
+use super::is_in_bounds;
+use image::Luma;
+use imageproc::definitions::Image;
+use imageproc::region_labelling::Connectivity;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    fn left(self) -> Heading {
+        use Heading::*;
+        match self {
+            North => West,
+            West => South,
+            South => East,
+            East => North,
+        }
+    }
+
+    fn right(self) -> Heading {
+        use Heading::*;
+        match self {
+            North => East,
+            East => South,
+            South => West,
+            West => North,
+        }
+    }
+
+    fn step(self) -> (i32, i32) {
+        use Heading::*;
+        match self {
+            North => (0, -1),
+            East => (1, 0),
+            South => (0, 1),
+            West => (-1, 0),
+        }
+    }
+}
+
+/// Walks the closed loop of cracks (pixel edges) separating `foreground_color` from
+/// everything else, keeping the foreground on the right-hand side. Corners are
+/// addressed so that pixel `(x, y)` has its top-left corner at `(x, y)`.
+///
+/// The walk starts on the crack leaving corner `(x, y)` along `heading`, and calls
+/// `visit` with the starting corner and heading of every crack until it gets back there.
+/// Where two foreground pixels meet only at a corner, the walk joins them if
+/// `connectivity` is `Eight` and keeps them apart if it is `Four`.
+pub fn walk_cracks<F>(
+    image: &Image<Luma<u32>>,
+    foreground_color: Luma<u32>,
+    connectivity: Connectivity,
+    x: i32,
+    y: i32,
+    heading: Heading,
+    mut visit: F,
+) where
+    F: FnMut(i32, i32, Heading),
+{
+    let start = (x, y, heading);
+    let (mut x, mut y, mut heading) = start;
+    loop {
+        visit(x, y, heading);
+        let (dx, dy) = heading.step();
+        x += dx;
+        y += dy;
+        heading = next_heading(image, foreground_color, connectivity, x, y, heading);
+        if (x, y, heading) == start {
+            break;
+        }
+    }
+}
+
+fn next_heading(
+    image: &Image<Luma<u32>>,
+    foreground_color: Luma<u32>,
+    connectivity: Connectivity,
+    x: i32,
+    y: i32,
+    heading: Heading,
+) -> Heading {
+    use Heading::*;
+    let (ahead_left, ahead_right) = match heading {
+        North => ((x - 1, y - 1), (x, y - 1)),
+        East => ((x, y - 1), (x, y)),
+        South => ((x, y), (x - 1, y)),
+        West => ((x - 1, y), (x - 1, y - 1)),
+    };
+    let is_foreground = |(x, y): (i32, i32)| {
+        is_in_bounds(x, y, image) && *image.get_pixel(x as u32, y as u32) == foreground_color
+    };
+    match (is_foreground(ahead_left), is_foreground(ahead_right)) {
+        (true, true) => heading.left(),
+        (false, true) => heading,
+        (true, false) if connectivity == Connectivity::Eight => heading.left(),
+        (_, false) => heading.right(),
+    }
+}
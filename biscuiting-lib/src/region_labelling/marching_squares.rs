@@ -0,0 +1,65 @@
+use super::crack::{walk_cracks, Heading};
+use super::{is_in_bounds, Point};
+use image::{GrayImage, Luma};
+use imageproc::definitions::Image;
+use imageproc::region_labelling::Connectivity;
+
+/// Walks a border of `foreground_color` crack by crack, as `walk_cracks` does, and
+/// for each crack emits the point where the grey level crosses `threshold` on the
+/// line joining the centres of the region pixel and the pixel outside it.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_iso_contour(
+    image: &Image<Luma<u32>>,
+    levels: &GrayImage,
+    threshold: f32,
+    foreground_color: Luma<u32>,
+    connectivity: Connectivity,
+    x: u32,
+    y: u32,
+    heading: Heading,
+) -> Vec<Point<f32>> {
+    let mut points = Vec::new();
+    walk_cracks(
+        image,
+        foreground_color,
+        connectivity,
+        x as i32,
+        y as i32,
+        heading,
+        |x, y, heading| {
+            let (inside, outside) = match heading {
+                Heading::East => ((x, y), (x, y - 1)),
+                Heading::South => ((x - 1, y), (x, y)),
+                Heading::West => ((x - 1, y - 1), (x - 1, y)),
+                Heading::North => ((x, y - 1), (x - 1, y - 1)),
+            };
+            let fraction = crossing_fraction(levels, threshold, inside, outside);
+            points.push(Point::new(
+                inside.0 as f32 + 0.5 + fraction * (outside.0 - inside.0) as f32,
+                inside.1 as f32 + 0.5 + fraction * (outside.1 - inside.1) as f32,
+            ));
+        },
+    );
+    points
+}
+
+/// How far from the inside pixel's centre towards the outside pixel's centre the
+/// level crosses `threshold`. Where there is nothing to interpolate between,
+/// such as at the edge of the image, the crack itself is used.
+fn crossing_fraction(
+    levels: &GrayImage,
+    threshold: f32,
+    inside: (i32, i32),
+    outside: (i32, i32),
+) -> f32 {
+    if !is_in_bounds(outside.0, outside.1, levels) {
+        return 0.5;
+    }
+    let inside_level = levels.get_pixel(inside.0 as u32, inside.1 as u32)[0] as f32;
+    let outside_level = levels.get_pixel(outside.0 as u32, outside.1 as u32)[0] as f32;
+    if inside_level < threshold && outside_level >= threshold {
+        ((threshold - inside_level) / (outside_level - inside_level)).min(1.0)
+    } else {
+        0.5
+    }
+}
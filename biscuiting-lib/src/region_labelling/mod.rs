@@ -1,45 +1,531 @@
 use image::GenericImage;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+pub mod adjacency;
+pub mod chain_code;
+mod crack;
+mod marching_squares;
+pub mod moments;
+mod moore;
+pub mod properties;
+pub mod shape;
 mod turtle;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub struct Point<T: Copy + PartialEq + Eq> {
+pub struct Point<T: Copy + PartialEq> {
     pub x: T,
     pub y: T,
 }
 
-impl<T: Copy + PartialEq + Eq> Point<T> {
+impl<T: Copy + PartialEq> Point<T> {
     pub fn new(x: T, y: T) -> Point<T> {
         Point::<T> { x, y }
     }
 }
 
-use bit_set::BitSet;
-use image::Luma;
+use image::{GrayImage, Luma};
 use imageproc::definitions::Image;
+use imageproc::region_labelling::Connectivity;
+
+/// Links between contours, in the style of OpenCV's `RETR_TREE`: the parent of a
+/// hole is the outer border of the same region, and the parent of an outer border
+/// is the hole it sits in, if any. Siblings share a parent and are in raster order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct HierarchyNode {
+    pub next: Option<usize>,
+    pub previous: Option<usize>,
+    pub first_child: Option<usize>,
+    pub parent: Option<usize>,
+    pub is_hole: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContourHierarchy<P = Point<u32>> {
+    pub contours: Vec<Vec<P>>,
+    pub nodes: Vec<HierarchyNode>,
+}
+
+impl<P> ContourHierarchy<P> {
+    fn push(&mut self, points: Vec<P>, parent: Option<usize>, is_hole: bool) -> usize {
+        self.contours.push(points);
+        self.nodes.push(HierarchyNode {
+            parent,
+            is_hole,
+            ..HierarchyNode::default()
+        });
+        self.contours.len() - 1
+    }
+
+    /// The same contours in a new order, where `order` lists the old index of
+    /// each contour, with the links between them rebuilt.
+    fn reordered(self, order: &[usize]) -> ContourHierarchy<P> {
+        let mut positions = vec![0; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            positions[*old] = new;
+        }
+        let mut contours: Vec<Option<Vec<P>>> = self.contours.into_iter().map(Some).collect();
+        let mut hierarchy = ContourHierarchy {
+            contours: Vec::with_capacity(order.len()),
+            nodes: Vec::with_capacity(order.len()),
+        };
+        for old in order {
+            let node = self.nodes[*old];
+            hierarchy.push(
+                contours[*old].take().unwrap(),
+                node.parent.map(|parent| positions[parent]),
+                node.is_hole,
+            );
+        }
+        hierarchy.link_siblings();
+        hierarchy
+    }
+
+    fn link_siblings(&mut self) {
+        let mut last_children: HashMap<Option<usize>, usize> = HashMap::new();
+        for index in 0..self.nodes.len() {
+            let parent = self.nodes[index].parent;
+            match last_children.insert(parent, index) {
+                Some(previous) => {
+                    self.nodes[previous].next = Some(index);
+                    self.nodes[index].previous = Some(previous);
+                }
+                None => {
+                    if let Some(parent) = parent {
+                        self.nodes[parent].first_child = Some(index);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How the points of a contour are placed along a border.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tracing {
+    /// The centres of the boundary pixels, so a single pixel is a single point.
+    PixelCentres,
+    /// The corners of the cracks between region and non-region pixels, so contours
+    /// enclose the full area of the region and neighbours share their edges.
+    Cracks,
+    /// The centres of the boundary pixels in the order a walk around the border
+    /// visits them, so pinch points appear once for each time they are passed.
+    PixelWalk,
+}
+
+struct Tracer {
+    tracing: Tracing,
+    connectivity: Connectivity,
+    turtle: turtle::Turtle,
+    points_seen: HashSet<Point<u32>>,
+}
 
-pub fn find_contours_in_luma(
+impl Tracer {
+    fn new(tracing: Tracing, connectivity: Connectivity) -> Tracer {
+        Tracer {
+            tracing,
+            connectivity,
+            turtle: turtle::Turtle::new(0, 0),
+            points_seen: HashSet::new(),
+        }
+    }
+
+    /// Traces the outer border of the region whose first pixel in raster order is `(x, y)`.
+    fn trace_outer(
+        &mut self,
+        image: &Image<Luma<u32>>,
+        foreground_color: Luma<u32>,
+        x: u32,
+        y: u32,
+    ) -> Vec<Point<u32>> {
+        match (self.tracing, self.connectivity) {
+            (Tracing::PixelCentres, Connectivity::Four) => {
+                self.turtle.reset(x, y);
+                self.trace_pixel_centres(image, foreground_color)
+            }
+            (Tracing::PixelCentres, Connectivity::Eight) => {
+                let walk = self.trace_pixel_walk(image, foreground_color, x, y, (-1, 0));
+                self.first_visits(walk)
+            }
+            (Tracing::Cracks, connectivity) => trace_crack_corners(
+                image,
+                foreground_color,
+                connectivity,
+                x,
+                y,
+                crack::Heading::East,
+            ),
+            (Tracing::PixelWalk, _) => {
+                self.trace_pixel_walk(image, foreground_color, x, y, (-1, 0))
+            }
+        }
+    }
+
+    /// Traces the hole border that starts on the east crack of pixel `(x, y)`.
+    fn trace_hole(
+        &mut self,
+        image: &Image<Luma<u32>>,
+        foreground_color: Luma<u32>,
+        x: u32,
+        y: u32,
+    ) -> Vec<Point<u32>> {
+        match (self.tracing, self.connectivity) {
+            (Tracing::PixelCentres, Connectivity::Four) => {
+                self.turtle.reset_heading(x, y, turtle::Direction::West);
+                self.trace_pixel_centres(image, foreground_color)
+            }
+            (Tracing::PixelCentres, Connectivity::Eight) => {
+                let walk = self.trace_pixel_walk(image, foreground_color, x, y, (1, 0));
+                self.first_visits(walk)
+            }
+            (Tracing::Cracks, connectivity) => trace_crack_corners(
+                image,
+                foreground_color,
+                connectivity,
+                x + 1,
+                y,
+                crack::Heading::South,
+            ),
+            (Tracing::PixelWalk, _) => self.trace_pixel_walk(image, foreground_color, x, y, (1, 0)),
+        }
+    }
+
+    fn trace_pixel_centres(
+        &mut self,
+        image: &Image<Luma<u32>>,
+        foreground_color: Luma<u32>,
+    ) -> Vec<Point<u32>> {
+        let mut points = Vec::new();
+        self.points_seen.clear();
+        trace_contour_luma(
+            &mut self.turtle,
+            image,
+            foreground_color,
+            &mut points,
+            &mut self.points_seen,
+        );
+        points
+    }
+
+    fn trace_pixel_walk(
+        &self,
+        image: &Image<Luma<u32>>,
+        foreground_color: Luma<u32>,
+        x: u32,
+        y: u32,
+        outside: (i32, i32),
+    ) -> Vec<Point<u32>> {
+        moore::trace_pixel_walk(image, foreground_color, self.connectivity, x, y, outside)
+    }
+
+    fn first_visits(&mut self, walk: Vec<Point<u32>>) -> Vec<Point<u32>> {
+        self.points_seen.clear();
+        walk.into_iter()
+            .filter(|point| self.points_seen.insert(*point))
+            .collect()
+    }
+}
+
+/// Keeps only the corners where the crack walk changes direction.
+fn trace_crack_corners(
+    image: &Image<Luma<u32>>,
+    foreground_color: Luma<u32>,
+    connectivity: Connectivity,
+    x: u32,
+    y: u32,
+    heading: crack::Heading,
+) -> Vec<Point<u32>> {
+    let mut points = Vec::new();
+    let mut last_heading = None;
+    crack::walk_cracks(
+        image,
+        foreground_color,
+        connectivity,
+        x as i32,
+        y as i32,
+        heading,
+        |x, y, heading| {
+            if last_heading != Some(heading) {
+                points.push(Point::new(x as u32, y as u32));
+                last_heading = Some(heading);
+            }
+        },
+    );
+    points
+}
+
+pub fn find_contour_hierarchy_in_luma(
     background_color: Luma<u32>,
     image: &Image<Luma<u32>>,
-) -> Vec<Vec<Point<u32>>> {
-    let mut colors_seen = BitSet::new();
-    let mut points_seen = HashSet::new();
-    let mut contours = Vec::new();
-    let mut turtle = turtle::Turtle::new(0, 0);
+    tracing: Tracing,
+    connectivity: Connectivity,
+) -> ContourHierarchy {
+    let mut tracer = Tracer::new(tracing, connectivity);
+    scan_contour_hierarchy(
+        background_color,
+        image,
+        connectivity,
+        |color, x, y, is_hole| {
+            if is_hole {
+                tracer.trace_hole(image, color, x, y)
+            } else {
+                tracer.trace_outer(image, color, x, y)
+            }
+        },
+    )
+}
+
+/// Traces the same borders as `find_contour_hierarchy_in_luma`, but with the
+/// outer borders in order of their labels rather than in raster order, for
+/// images whose labels were not numbered in raster order. Holes keep their
+/// raster order, after all the outer borders.
+pub fn find_contour_hierarchy_in_label_order(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    tracing: Tracing,
+    connectivity: Connectivity,
+) -> ContourHierarchy {
+    let mut tracer = Tracer::new(tracing, connectivity);
+    let mut labels = Vec::new();
+    let hierarchy = scan_contour_hierarchy(
+        background_color,
+        image,
+        connectivity,
+        |color, x, y, is_hole| {
+            labels.push(color[0]);
+            if is_hole {
+                tracer.trace_hole(image, color, x, y)
+            } else {
+                tracer.trace_outer(image, color, x, y)
+            }
+        },
+    );
+    let mut order: Vec<usize> = (0..labels.len()).collect();
+    order.sort_by_key(|index| {
+        let node = hierarchy.nodes[*index];
+        (node.is_hole, if node.is_hole { 0 } else { labels[*index] })
+    });
+    hierarchy.reordered(&order)
+}
+
+/// Traces the same borders as `find_contour_hierarchy_in_luma`, but places one
+/// point on each crack where the grey `levels` cross `threshold`, interpolating
+/// between the centres of the pixels either side of it. This is marching squares
+/// over `levels`, with each cell resolved the same way the labelling joined
+/// pixels, so `image` should label the pixels darker than `threshold`. Points
+/// use the `Tracing::Cracks` convention: the centre of pixel `(x, y)` is at
+/// `(x + 0.5, y + 0.5)`.
+pub fn find_iso_contour_hierarchy_in_luma(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    levels: &GrayImage,
+    threshold: f32,
+    connectivity: Connectivity,
+) -> ContourHierarchy<Point<f32>> {
+    scan_contour_hierarchy(
+        background_color,
+        image,
+        connectivity,
+        |color, x, y, is_hole| {
+            let (x, y, heading) = if is_hole {
+                (x + 1, y, crack::Heading::South)
+            } else {
+                (x, y, crack::Heading::East)
+            };
+            marching_squares::trace_iso_contour(
+                image,
+                levels,
+                threshold,
+                color,
+                connectivity,
+                x,
+                y,
+                heading,
+            )
+        },
+    )
+}
+
+/// Follows the Suzuki–Abe scheme: borders are traced as they are met in raster
+/// order, and every crack on a traced border remembers which contour it belongs
+/// to. Scanning along a row then crosses borders in nesting order, so a stack of
+/// the contours enclosing the current pixel gives the parent of each new border.
+///
+/// `trace` is given the region colour and the pixel a border starts from, which
+/// for a hole is the pixel on the west side of the hole's first crack.
+fn scan_contour_hierarchy<P, F>(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    connectivity: Connectivity,
+    mut trace: F,
+) -> ContourHierarchy<P>
+where
+    F: FnMut(Luma<u32>, u32, u32, bool) -> Vec<P>,
+{
+    let mut hierarchy = ContourHierarchy {
+        contours: Vec::new(),
+        nodes: Vec::new(),
+    };
+    let mut crack_contours = CrackContours::new(image);
+    let foreground_at = |x: u32, y: u32| {
+        if x < image.width() {
+            Some(*image.get_pixel(x, y)).filter(|color| *color != background_color)
+        } else {
+            None
+        }
+    };
     for y in 0..image.height() {
-        for x in 0..image.width() {
-            let color = image.get_pixel(x, y);
-            if !colors_seen.contains(color[0] as usize) && *color != background_color {
-                colors_seen.insert(color[0] as usize);
-                points_seen.clear();
-                let mut contour = Vec::new();
-                turtle.reset(x, y);
-                trace_contour_luma(&mut turtle, image, *color, &mut contour, &mut points_seen);
-                contours.push(contour);
+        let mut enclosing: Vec<usize> = Vec::new();
+        for x in 0..=image.width() {
+            let west = if x > 0 { foreground_at(x - 1, y) } else { None };
+            let east = foreground_at(x, y);
+            if west == east {
+                continue;
+            }
+            if let Some(color) = west {
+                let index = pixel_index(image, x - 1, y);
+                if crack_contours.east[index].is_none() {
+                    let hole = trace(color, x - 1, y, true);
+                    let contour = hierarchy.push(hole, enclosing.last().copied(), true);
+                    crack_contours.mark(
+                        image,
+                        color,
+                        connectivity,
+                        (x, y, crack::Heading::South),
+                        contour,
+                    );
+                }
+                let contour = crack_contours.east[index].unwrap();
+                if hierarchy.nodes[contour].is_hole {
+                    enclosing.push(contour);
+                } else {
+                    enclosing.pop();
+                }
+            }
+            if let Some(color) = east {
+                let index = pixel_index(image, x, y);
+                if crack_contours.west[index].is_none() {
+                    let outer = trace(color, x, y, false);
+                    let contour = hierarchy.push(outer, enclosing.last().copied(), false);
+                    crack_contours.mark(
+                        image,
+                        color,
+                        connectivity,
+                        (x, y, crack::Heading::East),
+                        contour,
+                    );
+                }
+                let contour = crack_contours.west[index].unwrap();
+                if hierarchy.nodes[contour].is_hole {
+                    enclosing.pop();
+                } else {
+                    enclosing.push(contour);
+                }
+            }
+        }
+    }
+    hierarchy.link_siblings();
+    hierarchy
+}
+
+/// The contour that each pixel's east and west cracks were traced as part of.
+struct CrackContours {
+    east: Vec<Option<usize>>,
+    west: Vec<Option<usize>>,
+}
+
+impl CrackContours {
+    fn new(image: &Image<Luma<u32>>) -> CrackContours {
+        let num_pixels = (image.width() * image.height()) as usize;
+        CrackContours {
+            east: vec![None; num_pixels],
+            west: vec![None; num_pixels],
+        }
+    }
+
+    fn mark(
+        &mut self,
+        image: &Image<Luma<u32>>,
+        foreground_color: Luma<u32>,
+        connectivity: Connectivity,
+        (x, y, heading): (u32, u32, crack::Heading),
+        contour: usize,
+    ) {
+        crack::walk_cracks(
+            image,
+            foreground_color,
+            connectivity,
+            x as i32,
+            y as i32,
+            heading,
+            |x, y, heading| match heading {
+                crack::Heading::South => {
+                    self.east[pixel_index(image, (x - 1) as u32, y as u32)] = Some(contour)
+                }
+                crack::Heading::North => {
+                    self.west[pixel_index(image, x as u32, (y - 1) as u32)] = Some(contour)
+                }
+                _ => {}
+            },
+        );
+    }
+}
+
+/// Clears every region of a labelled image whose entry in `keep` is false, and
+/// renumbers the remaining regions from 1 in their original order. The region
+/// labelled `n` has its entry at index `n - 1`.
+pub fn retain_regions(background_color: Luma<u32>, image: &mut Image<Luma<u32>>, keep: &[bool]) {
+    let mut next_label = 0;
+    let new_labels: Vec<Option<u32>> = keep
+        .iter()
+        .map(|keep| {
+            if *keep {
+                next_label += 1;
+                Some(next_label)
+            } else {
+                None
             }
+        })
+        .collect();
+    for pixel in image.pixels_mut() {
+        if *pixel != background_color {
+            *pixel = match new_labels[pixel[0] as usize - 1] {
+                Some(label) => Luma([label]),
+                None => background_color,
+            };
         }
     }
-    contours
+}
+
+/// Whether the crack corner `(x, y)` is where borders between different pairs of
+/// regions meet: three or more colors around it, or two meeting only diagonally.
+/// Pixels outside the image count as `background_color`.
+pub fn is_junction(background_color: Luma<u32>, image: &Image<Luma<u32>>, x: u32, y: u32) -> bool {
+    let color_at = |x: i32, y: i32| {
+        if is_in_bounds(x, y, image) {
+            *image.get_pixel(x as u32, y as u32)
+        } else {
+            background_color
+        }
+    };
+    let (x, y) = (x as i32, y as i32);
+    let (top_left, top_right) = (color_at(x - 1, y - 1), color_at(x, y - 1));
+    let (bottom_left, bottom_right) = (color_at(x - 1, y), color_at(x, y));
+    let mut colors = vec![top_left, top_right, bottom_left, bottom_right];
+    colors.sort_by_key(|color| color[0]);
+    colors.dedup();
+    colors.len() > 2
+        || (top_left == bottom_right && top_right == bottom_left && top_left != top_right)
+}
+
+/// Whether arcs of borders shared between regions should end at the crack
+/// corner `(x, y)`: at junctions, and at the corners of the image so that no arc
+/// cuts across one.
+pub fn is_arc_end(background_color: Luma<u32>, image: &Image<Luma<u32>>, x: u32, y: u32) -> bool {
+    let is_image_corner = (x == 0 || x == image.width()) && (y == 0 || y == image.height());
+    is_image_corner || is_junction(background_color, image, x, y)
+}
+
+fn pixel_index(image: &Image<Luma<u32>>, x: u32, y: u32) -> usize {
+    (y * image.width() + x) as usize
 }
 
 fn trace_contour_luma(
@@ -82,6 +568,44 @@ mod tests {
     use super::*;
     use image::Luma;
 
+    fn outer_contours(
+        background_color: Luma<u32>,
+        image: &Image<Luma<u32>>,
+    ) -> Vec<Vec<Point<u32>>> {
+        contours_with_holes(background_color, image)
+            .into_iter()
+            .map(|(outer, _)| outer)
+            .collect()
+    }
+
+    type ContourWithHoles = (Vec<Point<u32>>, Vec<Vec<Point<u32>>>);
+
+    fn contours_with_holes(
+        background_color: Luma<u32>,
+        image: &Image<Luma<u32>>,
+    ) -> Vec<ContourWithHoles> {
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            image,
+            Tracing::PixelCentres,
+            Connectivity::Four,
+        );
+        let mut contours: Vec<ContourWithHoles> = Vec::new();
+        let mut contour_indexes: HashMap<usize, usize> = HashMap::new();
+        for (index, (points, node)) in hierarchy.contours.iter().zip(&hierarchy.nodes).enumerate() {
+            match node.parent {
+                Some(parent) if node.is_hole => {
+                    contours[contour_indexes[&parent]].1.push(points.clone())
+                }
+                _ => {
+                    contour_indexes.insert(index, contours.len());
+                    contours.push((points.clone(), Vec::new()));
+                }
+            }
+        }
+        contours
+    }
+
     #[test]
     fn test_with_single_pixel() {
         let image = gray_image!(type: u32,
@@ -93,7 +617,7 @@ mod tests {
 
         let expected_contour = vec![Point::new(1, 1)];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -115,7 +639,7 @@ mod tests {
             Point::new(1, 2),
         ];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -141,11 +665,644 @@ mod tests {
             Point::new(1, 2),
         ];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
 
+    #[test]
+    fn test_with_holes_finds_hole() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0;
+            0, 255, 255, 255;
+            0, 255,   0, 255;
+            0, 255, 255, 255);
+
+        let background_color = Luma([0u32; 1]);
+
+        let contours = contours_with_holes(background_color, &image);
+
+        assert_eq!(1, contours.len());
+        assert_eq!(outer_contours(background_color, &image)[0], contours[0].0);
+        assert_eq!(
+            vec![vec![
+                Point::new(1, 2),
+                Point::new(1, 3),
+                Point::new(2, 3),
+                Point::new(3, 2),
+                Point::new(3, 1),
+                Point::new(2, 1),
+            ]],
+            contours[0].1
+        );
+    }
+
+    #[test]
+    fn test_with_holes_links_each_hole_to_its_region() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0,   0,   0,   0, 0;
+            0, 100, 100, 100,   0, 200, 200, 0;
+            0, 100,   0, 100,   0, 200,   0, 0;
+            0, 100, 100, 100,   0, 200, 200, 0;
+            0, 100,   0, 100,   0,   0,   0, 0;
+            0, 100, 100, 100,   0,   0,   0, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let contours = contours_with_holes(background_color, &image);
+
+        assert_eq!(2, contours.len());
+        assert_eq!(2, contours[0].1.len());
+        assert_eq!(Point::new(1, 2), contours[0].1[0][0]);
+        assert_eq!(Point::new(1, 4), contours[0].1[1][0]);
+        assert!(contours[1].1.is_empty());
+    }
+
+    #[test]
+    fn test_with_holes_ignores_gap_open_to_image_edge() {
+        let image = gray_image!(type: u32,
+            255, 255, 255;
+            255,   0,   0;
+            255, 255, 255);
+
+        let background_color = Luma([0u32; 1]);
+
+        let contours = contours_with_holes(background_color, &image);
+
+        assert_eq!(1, contours.len());
+        assert!(contours[0].1.is_empty());
+    }
+
+    #[test]
+    fn test_with_holes_keeps_island_inside_hole_separate() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0,   0,   0, 0;
+            0, 100, 100, 100, 100, 100, 0;
+            0, 100,   0,   0,   0, 100, 0;
+            0, 100,   0, 200,   0, 100, 0;
+            0, 100,   0,   0,   0, 100, 0;
+            0, 100, 100, 100, 100, 100, 0;
+            0,   0,   0,   0,   0,   0, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let contours = contours_with_holes(background_color, &image);
+
+        assert_eq!(2, contours.len());
+        assert_eq!(1, contours[0].1.len());
+        assert_eq!(vec![Point::new(3, 3)], contours[1].0);
+        assert!(contours[1].1.is_empty());
+    }
+
+    #[test]
+    fn test_hierarchy_with_nested_regions() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0,   0,   0,   0,   0,   0;
+            0, 100, 100, 100, 100, 100, 100, 100,   0;
+            0, 100,   0,   0,   0,   0,   0, 100,   0;
+            0, 100,   0, 200, 200, 200,   0, 100,   0;
+            0, 100,   0, 200,   0, 200,   0, 100,   0;
+            0, 100,   0, 200, 200, 200,   0, 100,   0;
+            0, 100,   0,   0,   0,   0,   0, 100,   0;
+            0, 100, 100, 100, 100, 100, 100, 100,   0;
+            0,   0,   0,   0,   0,   0,   0,   0, 250);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelCentres,
+            Connectivity::Four,
+        );
+
+        assert_eq!(5, hierarchy.contours.len());
+        assert_eq!(Point::new(1, 1), hierarchy.contours[0][0]);
+        assert_eq!(Point::new(1, 2), hierarchy.contours[1][0]);
+        assert_eq!(Point::new(3, 3), hierarchy.contours[2][0]);
+        assert_eq!(Point::new(3, 4), hierarchy.contours[3][0]);
+        assert_eq!(vec![Point::new(8, 8)], hierarchy.contours[4]);
+        assert_eq!(
+            vec![
+                HierarchyNode {
+                    next: Some(4),
+                    previous: None,
+                    first_child: Some(1),
+                    parent: None,
+                    is_hole: false,
+                },
+                HierarchyNode {
+                    next: None,
+                    previous: None,
+                    first_child: Some(2),
+                    parent: Some(0),
+                    is_hole: true,
+                },
+                HierarchyNode {
+                    next: None,
+                    previous: None,
+                    first_child: Some(3),
+                    parent: Some(1),
+                    is_hole: false,
+                },
+                HierarchyNode {
+                    next: None,
+                    previous: None,
+                    first_child: None,
+                    parent: Some(2),
+                    is_hole: true,
+                },
+                HierarchyNode {
+                    next: None,
+                    previous: Some(0),
+                    first_child: None,
+                    parent: None,
+                    is_hole: false,
+                },
+            ],
+            hierarchy.nodes
+        );
+    }
+
+    #[test]
+    fn test_hierarchy_with_siblings_inside_one_hole() {
+        let image = gray_image!(type: u32,
+            100, 100, 100, 100, 100, 100;
+            100,   0,   0,   0,   0, 100;
+            100,   0, 200,   0, 150, 100;
+            100,   0,   0,   0,   0, 100;
+            100, 100, 100, 100, 100, 100);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelCentres,
+            Connectivity::Four,
+        );
+
+        assert_eq!(4, hierarchy.nodes.len());
+        assert_eq!(Some(2), hierarchy.nodes[1].first_child);
+        assert_eq!(Some(1), hierarchy.nodes[2].parent);
+        assert_eq!(Some(1), hierarchy.nodes[3].parent);
+        assert_eq!(Some(3), hierarchy.nodes[2].next);
+        assert_eq!(Some(2), hierarchy.nodes[3].previous);
+    }
+
+    #[test]
+    fn test_cracks_with_single_pixel() {
+        let image = gray_image!(type: u32,
+            0,   0,  0;
+            0, 255,  0;
+            0,   0,  0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![vec![
+                Point::new(1, 1),
+                Point::new(2, 1),
+                Point::new(2, 2),
+                Point::new(1, 2),
+            ]],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_cracks_with_l_shape() {
+        let image = gray_image!(type: u32,
+            0,   0,   0, 0;
+            0, 255,   0, 0;
+            0, 255, 255, 0;
+            0,   0,   0, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![vec![
+                Point::new(1, 1),
+                Point::new(2, 1),
+                Point::new(2, 2),
+                Point::new(3, 2),
+                Point::new(3, 3),
+                Point::new(1, 3),
+            ]],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_cracks_with_hole() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0;
+            0, 255, 255, 255;
+            0, 255,   0, 255;
+            0, 255, 255, 255);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![
+                vec![
+                    Point::new(1, 1),
+                    Point::new(4, 1),
+                    Point::new(4, 4),
+                    Point::new(1, 4),
+                ],
+                vec![
+                    Point::new(2, 2),
+                    Point::new(2, 3),
+                    Point::new(3, 3),
+                    Point::new(3, 2),
+                ],
+            ],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_cracks_with_neighbouring_regions_share_an_edge() {
+        let image = gray_image!(type: u32,
+            100, 100, 200;
+            100, 100, 200);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![
+                vec![
+                    Point::new(0, 0),
+                    Point::new(2, 0),
+                    Point::new(2, 2),
+                    Point::new(0, 2),
+                ],
+                vec![
+                    Point::new(2, 0),
+                    Point::new(3, 0),
+                    Point::new(3, 2),
+                    Point::new(2, 2),
+                ],
+            ],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_label_order_puts_outer_borders_in_label_order() {
+        let image = gray_image!(type: u32,
+            0, 0, 0, 2;
+            1, 1, 1, 0;
+            1, 0, 1, 0;
+            1, 1, 1, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_label_order(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        assert_eq!(3, hierarchy.contours.len());
+        assert_eq!(Point::new(0, 1), hierarchy.contours[0][0]);
+        assert_eq!(Point::new(3, 0), hierarchy.contours[1][0]);
+        assert_eq!(
+            vec![
+                HierarchyNode {
+                    next: Some(1),
+                    first_child: Some(2),
+                    ..HierarchyNode::default()
+                },
+                HierarchyNode {
+                    previous: Some(0),
+                    ..HierarchyNode::default()
+                },
+                HierarchyNode {
+                    parent: Some(0),
+                    is_hole: true,
+                    ..HierarchyNode::default()
+                },
+            ],
+            hierarchy.nodes
+        );
+    }
+
+    #[test]
+    fn test_iso_contour_interpolates_between_pixel_centres() {
+        let image = gray_image!(type: u32,
+            0, 0, 0;
+            0, 1, 0;
+            0, 0, 0);
+        let levels = gray_image!(
+            200, 200, 200;
+            100,   0, 200;
+            200, 200, 200);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_iso_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            &levels,
+            50.0,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![vec![
+                Point::new(1.5, 1.25),
+                Point::new(1.75, 1.5),
+                Point::new(1.5, 1.75),
+                Point::new(1.0, 1.5),
+            ]],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_iso_contour_uses_crack_at_image_edge() {
+        let image = gray_image!(type: u32,
+            1, 0;
+            0, 0);
+        let levels = gray_image!(
+             0, 200;
+           200, 200);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_iso_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            &levels,
+            100.0,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![vec![
+                Point::new(0.5, 0.0),
+                Point::new(1.0, 0.5),
+                Point::new(0.5, 1.0),
+                Point::new(0.0, 0.5),
+            ]],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_pixel_walk_with_single_pixel() {
+        let image = gray_image!(type: u32,
+            0,   0,  0;
+            0, 255,  0;
+            0,   0,  0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Four,
+        );
+
+        assert_eq!(vec![vec![Point::new(1, 1)]], hierarchy.contours);
+    }
+
+    #[test]
+    fn test_pixel_walk_revisits_pinch_point() {
+        let image = gray_image!(type: u32,
+            0,   0,   0, 0;
+            0, 255,   0, 0;
+            0, 255, 255, 0;
+            0,   0,   0, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![vec![
+                Point::new(1, 1),
+                Point::new(1, 2),
+                Point::new(2, 2),
+                Point::new(1, 2),
+            ]],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_pixel_walk_goes_out_and_back_along_peninsula() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0,   0;
+            0, 255, 255, 255, 255;
+            0, 255, 255,   0,   0;
+            0,   0,   0,   0,   0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![vec![
+                Point::new(1, 1),
+                Point::new(2, 1),
+                Point::new(3, 1),
+                Point::new(4, 1),
+                Point::new(3, 1),
+                Point::new(2, 1),
+                Point::new(2, 2),
+                Point::new(1, 2),
+            ]],
+            hierarchy.contours
+        );
+    }
+
+    #[test]
+    fn test_pixel_walk_with_hole() {
+        let image = gray_image!(type: u32,
+            0,   0,   0,   0;
+            0, 255, 255, 255;
+            0, 255,   0, 255;
+            0, 255, 255, 255);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Four,
+        );
+
+        assert_eq!(
+            vec![
+                Point::new(1, 2),
+                Point::new(1, 3),
+                Point::new(2, 3),
+                Point::new(3, 3),
+                Point::new(3, 2),
+                Point::new(3, 1),
+                Point::new(2, 1),
+                Point::new(1, 1),
+            ],
+            hierarchy.contours[1]
+        );
+    }
+
+    #[test]
+    fn test_eight_connectivity_joins_diagonal_line() {
+        let image = gray_image!(type: u32,
+            255,   0,   0;
+              0, 255,   0;
+              0,   0, 255);
+
+        let background_color = Luma([0u32; 1]);
+
+        let four = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelCentres,
+            Connectivity::Four,
+        );
+        let eight = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelCentres,
+            Connectivity::Eight,
+        );
+
+        assert_eq!(3, four.contours.len());
+        assert_eq!(
+            vec![vec![Point::new(0, 0), Point::new(1, 1), Point::new(2, 2)]],
+            eight.contours
+        );
+    }
+
+    #[test]
+    fn test_eight_connectivity_encloses_hole_with_diagonal_ring() {
+        let image = gray_image!(type: u32,
+              0, 255,   0;
+            255,   0, 255;
+              0, 255,   0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let four = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+        let eight = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Eight,
+        );
+
+        assert_eq!(4, four.contours.len());
+        assert!(four.nodes.iter().all(|node| !node.is_hole));
+        assert_eq!(2, eight.contours.len());
+        assert!(eight.nodes[1].is_hole);
+        assert_eq!(Some(0), eight.nodes[1].parent);
+        assert_eq!(
+            vec![
+                Point::new(1, 1),
+                Point::new(1, 2),
+                Point::new(2, 2),
+                Point::new(2, 1),
+            ],
+            eight.contours[1]
+        );
+    }
+
+    #[test]
+    fn test_pixel_walk_with_eight_connectivity_steps_diagonally() {
+        let image = gray_image!(type: u32,
+              0, 255,   0;
+            255,   0, 255;
+              0, 255,   0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::PixelWalk,
+            Connectivity::Eight,
+        );
+
+        assert_eq!(
+            vec![
+                Point::new(1, 0),
+                Point::new(2, 1),
+                Point::new(1, 2),
+                Point::new(0, 1),
+            ],
+            hierarchy.contours[0]
+        );
+    }
+
+    #[test]
+    fn test_retain_regions_renumbers_kept_regions() {
+        let mut image = gray_image!(type: u32,
+            1, 0, 2, 0, 3;
+            1, 0, 2, 0, 3);
+
+        let background_color = Luma([0u32; 1]);
+
+        retain_regions(background_color, &mut image, &[false, true, true]);
+
+        assert_eq!(
+            gray_image!(type: u32,
+                0, 0, 1, 0, 2;
+                0, 0, 1, 0, 2),
+            image
+        );
+    }
+
     #[test]
     fn test_with_l_shape_example_1() {
         let image = gray_image!(type: u32,
@@ -158,7 +1315,7 @@ mod tests {
 
         let expected_contour = vec![Point::new(1, 1), Point::new(2, 1), Point::new(2, 2)];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -175,7 +1332,7 @@ mod tests {
 
         let expected_contour = vec![Point::new(1, 1), Point::new(2, 1), Point::new(1, 2)];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -192,7 +1349,7 @@ mod tests {
 
         let expected_contour = vec![Point::new(1, 1), Point::new(1, 2), Point::new(2, 2)];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -209,7 +1366,7 @@ mod tests {
 
         let expected_contour = vec![Point::new(2, 1), Point::new(2, 2), Point::new(1, 2)];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -231,7 +1388,7 @@ mod tests {
             Point::new(2, 2),
         ];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -253,7 +1410,7 @@ mod tests {
             Point::new(1, 3),
         ];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
@@ -268,13 +1425,13 @@ mod tests {
 
         let expected_contour = vec![Point::new(0, 0)];
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![expected_contour.clone()], contours);
     }
 
     #[test]
-    fn test_find_contours_in_luma_with_multiple_contours_in_large_example() {
+    fn test_outer_contours_with_multiple_contours_in_large_example() {
         let image = gray_image!(type: u32,
             0,   0, 100, 100;
             0,   0, 100, 100;
@@ -283,7 +1440,7 @@ mod tests {
 
         let background_color = Luma([0u32; 1]);
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(
             vec![
@@ -309,7 +1466,7 @@ mod tests {
     }
 
     #[test]
-    fn test_find_contours_in_luma_with_multiple_contours_in_minimal_example() {
+    fn test_outer_contours_with_multiple_contours_in_minimal_example() {
         let image = gray_image!(type: u32,
             0,   0,   0, 100;
             0,   0,   0, 0;
@@ -318,7 +1475,7 @@ mod tests {
 
         let background_color = Luma([0u32; 1]);
 
-        let contours = find_contours_in_luma(background_color, &image);
+        let contours = outer_contours(background_color, &image);
 
         assert_eq!(vec![Point::new(3, 0)], contours[0]);
         assert_eq!(vec![Point::new(0, 3)], contours[1]);
@@ -0,0 +1,189 @@
+use image::Luma;
+use imageproc::definitions::Image;
+
+/// Raw image moments `m_pq`, the sum of `x^p * y^q` over the pixels of a
+/// region, up to third order. `x` and `y` are pixel indexes.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct RawMoments {
+    pub m00: f64,
+    pub m10: f64,
+    pub m01: f64,
+    pub m20: f64,
+    pub m11: f64,
+    pub m02: f64,
+    pub m30: f64,
+    pub m21: f64,
+    pub m12: f64,
+    pub m03: f64,
+}
+
+/// Moments about the centroid, which are unchanged when a region moves.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct CentralMoments {
+    pub mu20: f64,
+    pub mu11: f64,
+    pub mu02: f64,
+    pub mu30: f64,
+    pub mu21: f64,
+    pub mu12: f64,
+    pub mu03: f64,
+}
+
+/// The ellipse with the same second moments as a region.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ellipse {
+    pub major_axis_length: f64,
+    pub minor_axis_length: f64,
+    /// Radians from the `x` axis towards the `y` axis, in `-π/2..=π/2`.
+    pub orientation: f64,
+    /// 0 for a circle, approaching 1 as the ellipse flattens.
+    pub eccentricity: f64,
+}
+
+impl RawMoments {
+    fn add(&mut self, x: f64, y: f64) {
+        self.m00 += 1.0;
+        self.m10 += x;
+        self.m01 += y;
+        self.m20 += x * x;
+        self.m11 += x * y;
+        self.m02 += y * y;
+        self.m30 += x * x * x;
+        self.m21 += x * x * y;
+        self.m12 += x * y * y;
+        self.m03 += y * y * y;
+    }
+
+    pub fn central(&self) -> CentralMoments {
+        let (cx, cy) = (self.m10 / self.m00, self.m01 / self.m00);
+        CentralMoments {
+            mu20: self.m20 - cx * self.m10,
+            mu11: self.m11 - cx * self.m01,
+            mu02: self.m02 - cy * self.m01,
+            mu30: self.m30 - 3.0 * cx * self.m20 + 2.0 * cx * cx * self.m10,
+            mu21: self.m21 - 2.0 * cx * self.m11 - cy * self.m20 + 2.0 * cx * cx * self.m01,
+            mu12: self.m12 - 2.0 * cy * self.m11 - cx * self.m02 + 2.0 * cy * cy * self.m10,
+            mu03: self.m03 - 3.0 * cy * self.m02 + 2.0 * cy * cy * self.m01,
+        }
+    }
+}
+
+impl CentralMoments {
+    /// The seven Hu invariants, which are also unchanged by scaling and rotation.
+    /// `m00` is the area of the region.
+    pub fn hu(&self, m00: f64) -> [f64; 7] {
+        let eta = |mu: f64, order: i32| mu / m00.powf(1.0 + order as f64 / 2.0);
+        let (n20, n11, n02) = (eta(self.mu20, 2), eta(self.mu11, 2), eta(self.mu02, 2));
+        let (n30, n21) = (eta(self.mu30, 3), eta(self.mu21, 3));
+        let (n12, n03) = (eta(self.mu12, 3), eta(self.mu03, 3));
+        let (a, b) = (n30 + n12, n21 + n03);
+        [
+            n20 + n02,
+            (n20 - n02).powi(2) + 4.0 * n11 * n11,
+            (n30 - 3.0 * n12).powi(2) + (3.0 * n21 - n03).powi(2),
+            a * a + b * b,
+            (n30 - 3.0 * n12) * a * (a * a - 3.0 * b * b)
+                + (3.0 * n21 - n03) * b * (3.0 * a * a - b * b),
+            (n20 - n02) * (a * a - b * b) + 4.0 * n11 * a * b,
+            (3.0 * n21 - n03) * a * (a * a - 3.0 * b * b)
+                - (n30 - 3.0 * n12) * b * (3.0 * a * a - b * b),
+        ]
+    }
+
+    /// The equivalent ellipse, from the eigenvalues of the covariance matrix.
+    /// `m00` is the area of the region.
+    pub fn ellipse(&self, m00: f64) -> Ellipse {
+        let (a, b, c) = (self.mu20 / m00, self.mu11 / m00, self.mu02 / m00);
+        let spread = (4.0 * b * b + (a - c).powi(2)).sqrt();
+        let major = (a + c + spread) / 2.0;
+        let minor = ((a + c - spread) / 2.0).max(0.0);
+        Ellipse {
+            major_axis_length: 4.0 * major.sqrt(),
+            minor_axis_length: 4.0 * minor.sqrt(),
+            orientation: 0.5 * (2.0 * b).atan2(a - c),
+            eccentricity: if major > 0.0 {
+                (1.0 - minor / major).sqrt()
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Raw moments of every region in a labelled image, in a single pass. The
+/// region labelled `n` is at index `n - 1`.
+pub fn raw_moments(background_color: Luma<u32>, image: &Image<Luma<u32>>) -> Vec<RawMoments> {
+    let mut moments: Vec<RawMoments> = Vec::new();
+    for (x, y, color) in image.enumerate_pixels() {
+        if *color == background_color {
+            continue;
+        }
+        let index = color[0] as usize - 1;
+        if index >= moments.len() {
+            moments.resize(index + 1, RawMoments::default());
+        }
+        moments[index].add(x as f64, y as f64);
+    }
+    moments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moments_of_horizontal_bar() {
+        let image = gray_image!(type: u32,
+            0, 0, 0, 0;
+            0, 1, 1, 1);
+
+        let background_color = Luma([0u32; 1]);
+
+        let raw = raw_moments(background_color, &image)[0];
+        let central = raw.central();
+
+        assert_eq!(3.0, raw.m00);
+        assert_eq!(6.0, raw.m10);
+        assert_eq!(3.0, raw.m01);
+        assert_eq!(2.0, central.mu20);
+        assert_eq!(0.0, central.mu11);
+        assert_eq!(0.0, central.mu02);
+        assert_eq!(0.0, central.mu30);
+        let hu = central.hu(raw.m00);
+        assert!((hu[0] - 2.0 / 9.0).abs() < 1e-9);
+        assert!((hu[1] - 4.0 / 81.0).abs() < 1e-9);
+        let ellipse = central.ellipse(raw.m00);
+        assert!((ellipse.major_axis_length - 4.0 * (2.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!(0.0, ellipse.minor_axis_length);
+        assert_eq!(0.0, ellipse.orientation);
+        assert_eq!(1.0, ellipse.eccentricity);
+    }
+
+    #[test]
+    fn test_hu_moments_do_not_change_with_rotation() {
+        let image = gray_image!(type: u32,
+            1, 1, 1, 0, 2, 2;
+            1, 0, 0, 0, 0, 2;
+            0, 0, 0, 0, 0, 2);
+
+        let background_color = Luma([0u32; 1]);
+
+        let moments = raw_moments(background_color, &image);
+        let first = moments[0].central();
+        let second = moments[1].central();
+
+        for (a, b) in first
+            .hu(moments[0].m00)
+            .iter()
+            .zip(second.hu(moments[1].m00).iter())
+        {
+            assert!((a - b).abs() < 1e-9);
+        }
+        let first = first.ellipse(moments[0].m00);
+        let second = second.ellipse(moments[1].m00);
+        assert!((first.major_axis_length - second.major_axis_length).abs() < 1e-9);
+        assert!((first.eccentricity - second.eccentricity).abs() < 1e-9);
+        assert!(first.orientation < 0.0);
+        assert!(second.orientation > 0.0);
+    }
+}
@@ -0,0 +1,102 @@
+use super::{is_in_bounds, Point};
+use image::Luma;
+use imageproc::definitions::Image;
+use imageproc::region_labelling::Connectivity;
+
+/// Neighbour offsets, clockwise from east.
+const FOUR_NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const EIGHT_NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Moore-neighbour tracing: from each boundary pixel, sweep its neighbours
+/// clockwise, starting just after one known to be outside the region, and step
+/// to the first one inside it. Every step is recorded, so pixels the border passes
+/// through more than once (necks, one-pixel peninsulas) appear once per visit.
+///
+/// The walk starts at `(x, y)`, whose neighbour at offset `outside` is not part of
+/// the region, and uses Jacob's stopping criterion: it ends when it is back at the
+/// start pixel and about to leave it the same way it first did. The start is not
+/// repeated at the end.
+pub fn trace_pixel_walk(
+    image: &Image<Luma<u32>>,
+    foreground_color: Luma<u32>,
+    connectivity: Connectivity,
+    x: u32,
+    y: u32,
+    outside: (i32, i32),
+) -> Vec<Point<u32>> {
+    let neighbours: &[(i32, i32)] = match connectivity {
+        Connectivity::Four => &FOUR_NEIGHBOURS,
+        Connectivity::Eight => &EIGHT_NEIGHBOURS,
+    };
+    let start = (x as i32, y as i32);
+    let mut points = vec![Point::new(x, y)];
+    let outside = neighbours
+        .iter()
+        .position(|offset| *offset == outside)
+        .expect("outside must be a neighbour");
+    let first_direction = match next_direction(
+        image,
+        foreground_color,
+        neighbours,
+        start,
+        (outside + 1) % neighbours.len(),
+    ) {
+        Some(direction) => direction,
+        None => return points,
+    };
+    let mut current = start;
+    let mut direction = first_direction;
+    loop {
+        let (dx, dy) = neighbours[direction];
+        current = (current.0 + dx, current.1 + dy);
+        let next = next_direction(
+            image,
+            foreground_color,
+            neighbours,
+            current,
+            search_from(neighbours.len(), direction),
+        )
+        .expect("walk stepped into a pixel with no way out");
+        if current == start && next == first_direction {
+            break;
+        }
+        points.push(Point::new(current.0 as u32, current.1 as u32));
+        direction = next;
+    }
+    points
+}
+
+/// Where to start sweeping after stepping in `direction`: just after the last
+/// neighbour of the previous pixel that was found to be outside the region.
+fn search_from(num_neighbours: usize, direction: usize) -> usize {
+    if num_neighbours == 8 && direction % 2 == 1 {
+        (direction + num_neighbours - 2) % num_neighbours
+    } else {
+        (direction + num_neighbours - 1) % num_neighbours
+    }
+}
+
+fn next_direction(
+    image: &Image<Luma<u32>>,
+    foreground_color: Luma<u32>,
+    neighbours: &[(i32, i32)],
+    (x, y): (i32, i32),
+    search_from: usize,
+) -> Option<usize> {
+    (0..neighbours.len())
+        .map(|offset| (search_from + offset) % neighbours.len())
+        .find(|direction| {
+            let (dx, dy) = neighbours[*direction];
+            is_in_bounds(x + dx, y + dy, image)
+                && *image.get_pixel((x + dx) as u32, (y + dy) as u32) == foreground_color
+        })
+}
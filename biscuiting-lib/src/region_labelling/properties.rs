@@ -0,0 +1,203 @@
+use super::is_in_bounds;
+use image::{GrayImage, Luma};
+use imageproc::definitions::Image;
+
+/// Measurements of one labelled region. Pixel `(x, y)` is taken to cover the
+/// square from `(x, y)` to `(x + 1, y + 1)`, as crack contours do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RegionProperties {
+    /// Number of pixels.
+    pub area: u32,
+    /// Number of pixel edges between the region and anything else.
+    pub perimeter: u32,
+    pub sum_x: u64,
+    pub sum_y: u64,
+    pub min_x: u32,
+    pub min_y: u32,
+    /// Exclusive, so a single pixel at `(x, y)` has `max_x` of `x + 1`.
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+impl RegionProperties {
+    fn new() -> RegionProperties {
+        RegionProperties {
+            area: 0,
+            perimeter: 0,
+            sum_x: 0,
+            sum_y: 0,
+            min_x: u32::MAX,
+            min_y: u32::MAX,
+            max_x: 0,
+            max_y: 0,
+        }
+    }
+
+    /// Whether any pixel of the region is on the edge of a `width` by `height` image.
+    pub fn touches_edge(&self, width: u32, height: u32) -> bool {
+        self.min_x == 0 || self.min_y == 0 || self.max_x == width || self.max_y == height
+    }
+
+    pub fn centroid(&self) -> (f32, f32) {
+        (
+            self.sum_x as f32 / self.area as f32 + 0.5,
+            self.sum_y as f32 / self.area as f32 + 0.5,
+        )
+    }
+}
+
+/// Properties of every region in a labelled image such as `connected_components`
+/// produces, in a single pass. The region labelled `n` is at index `n - 1`.
+pub fn region_properties(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+) -> Vec<RegionProperties> {
+    let mut properties: Vec<RegionProperties> = Vec::new();
+    let (width, height) = image.dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            let color = *image.get_pixel(x, y);
+            if color == background_color {
+                continue;
+            }
+            let index = color[0] as usize - 1;
+            if index >= properties.len() {
+                properties.resize(index + 1, RegionProperties::new());
+            }
+            let region = &mut properties[index];
+            region.area += 1;
+            region.sum_x += x as u64;
+            region.sum_y += y as u64;
+            region.min_x = region.min_x.min(x);
+            region.min_y = region.min_y.min(y);
+            region.max_x = region.max_x.max(x + 1);
+            region.max_y = region.max_y.max(y + 1);
+            let neighbours = [
+                (x as i32 + 1, y as i32),
+                (x as i32, y as i32 + 1),
+                (x as i32 - 1, y as i32),
+                (x as i32, y as i32 - 1),
+            ];
+            region.perimeter += neighbours
+                .iter()
+                .filter(|(x, y)| {
+                    !is_in_bounds(*x, *y, image) || *image.get_pixel(*x as u32, *y as u32) != color
+                })
+                .count() as u32;
+        }
+    }
+    properties
+}
+
+/// For every region of a labelled image, the classes in `classes` of the
+/// background pixels next to it, as a set of bits `1 << class`. The region
+/// labelled `n` is at index `n - 1`.
+pub fn border_classes(
+    background_color: Luma<u32>,
+    image: &Image<Luma<u32>>,
+    classes: &GrayImage,
+) -> Vec<u8> {
+    let mut border_classes: Vec<u8> = Vec::new();
+    for (x, y, color) in image.enumerate_pixels() {
+        if *color == background_color {
+            continue;
+        }
+        let index = color[0] as usize - 1;
+        if index >= border_classes.len() {
+            border_classes.resize(index + 1, 0);
+        }
+        let neighbours = [
+            (x as i32 + 1, y as i32),
+            (x as i32, y as i32 + 1),
+            (x as i32 - 1, y as i32),
+            (x as i32, y as i32 - 1),
+        ];
+        for (nx, ny) in neighbours
+            .iter()
+            .filter(|(x, y)| is_in_bounds(*x, *y, image))
+        {
+            if *image.get_pixel(*nx as u32, *ny as u32) == background_color {
+                border_classes[index] |= 1 << classes.get_pixel(*nx as u32, *ny as u32)[0];
+            }
+        }
+    }
+    border_classes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_properties() {
+        let image = gray_image!(type: u32,
+            1, 1, 0, 0;
+            1, 0, 0, 2;
+            0, 0, 2, 2);
+
+        let background_color = Luma([0u32; 1]);
+
+        let properties = region_properties(background_color, &image);
+
+        assert_eq!(
+            vec![
+                RegionProperties {
+                    area: 3,
+                    perimeter: 8,
+                    sum_x: 1,
+                    sum_y: 1,
+                    min_x: 0,
+                    min_y: 0,
+                    max_x: 2,
+                    max_y: 2,
+                },
+                RegionProperties {
+                    area: 3,
+                    perimeter: 8,
+                    sum_x: 8,
+                    sum_y: 5,
+                    min_x: 2,
+                    min_y: 1,
+                    max_x: 4,
+                    max_y: 3,
+                },
+            ],
+            properties
+        );
+        assert_eq!((1.0 / 3.0 + 0.5, 1.0 / 3.0 + 0.5), properties[0].centroid());
+    }
+
+    #[test]
+    fn test_region_properties_touches_edge() {
+        let image = gray_image!(type: u32,
+            0, 0, 0, 0;
+            0, 1, 0, 0;
+            0, 0, 0, 2);
+
+        let background_color = Luma([0u32; 1]);
+
+        let properties = region_properties(background_color, &image);
+
+        assert!(!properties[0].touches_edge(4, 3));
+        assert!(properties[1].touches_edge(4, 3));
+    }
+
+    #[test]
+    fn test_border_classes() {
+        let image = gray_image!(type: u32,
+            1, 0, 0, 0;
+            0, 0, 2, 0;
+            0, 0, 0, 0);
+        let classes = gray_image!(
+            2, 0, 1, 3;
+            1, 0, 2, 1;
+            0, 0, 0, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        assert_eq!(
+            vec![0b0011, 0b0011],
+            border_classes(background_color, &image, &classes)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use super::properties::RegionProperties;
+use super::Point;
+use std::f64::consts::PI;
+
+/// Scores of how regular a region is, each 1 for a perfect disc (Polsby–Popper)
+/// or for any convex region (solidity, convexity), and smaller otherwise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeMetrics {
+    /// `4πA / P²`, the isoperimetric quotient, of the polygon through the
+    /// middles of the outer border's cracks. That cuts each pixel corner
+    /// diagonally, so a rasterised disc scores close to 1 and a square close to
+    /// π/4 rather than both scoring as staircases. Holes do not count.
+    pub polsby_popper: f64,
+    pub hull_area: f64,
+    /// Area divided by the area of the convex hull.
+    pub solidity: f64,
+    /// Perimeter of the convex hull divided by that of the outer border, so
+    /// holes do not count.
+    pub convexity: f64,
+}
+
+/// The convex hull of `points` by Andrew's monotone chain, without collinear
+/// points, running clockwise on screen like the outer contours.
+pub fn convex_hull(points: &[Point<u32>]) -> Vec<Point<u32>> {
+    let mut sorted: Vec<(i64, i64)> = points
+        .iter()
+        .map(|point| (point.x as i64, point.y as i64))
+        .collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted
+            .into_iter()
+            .map(|(x, y)| Point::new(x as u32, y as u32))
+            .collect();
+    }
+    let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let half_hull = |points: &mut dyn Iterator<Item = &(i64, i64)>| {
+        let mut half: Vec<(i64, i64)> = Vec::new();
+        for point in points {
+            while half.len() >= 2 && cross(half[half.len() - 2], half[half.len() - 1], *point) <= 0
+            {
+                half.pop();
+            }
+            half.push(*point);
+        }
+        half.pop();
+        half
+    };
+    let mut hull = half_hull(&mut sorted.iter());
+    hull.extend(half_hull(&mut sorted.iter().rev()));
+    hull.into_iter()
+        .map(|(x, y)| Point::new(x as u32, y as u32))
+        .collect()
+}
+
+fn polygon_area(ring: &[(f64, f64)]) -> f64 {
+    let doubled: f64 = ring
+        .iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum();
+    doubled.abs() / 2.0
+}
+
+fn polygon_perimeter(ring: &[(f64, f64)]) -> f64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| (b.0 - a.0).hypot(b.1 - a.1))
+        .sum()
+}
+
+fn to_floats(ring: &[Point<u32>]) -> Vec<(f64, f64)> {
+    ring.iter()
+        .map(|point| (point.x as f64, point.y as f64))
+        .collect()
+}
+
+/// The middle of every crack along a crack contour, in order.
+fn crack_middles(ring: &[Point<u32>]) -> Vec<(f64, f64)> {
+    let mut middles = Vec::new();
+    for (start, end) in to_floats(ring)
+        .iter()
+        .zip(to_floats(ring).iter().cycle().skip(1))
+    {
+        let length = (end.0 - start.0).abs() + (end.1 - start.1).abs();
+        let (dx, dy) = ((end.0 - start.0) / length, (end.1 - start.1) / length);
+        for step in 0..length as u32 {
+            let along = step as f64 + 0.5;
+            middles.push((start.0 + along * dx, start.1 + along * dy));
+        }
+    }
+    middles
+}
+
+/// Shape metrics for a region from its properties and its outer crack contour.
+pub fn shape_metrics(properties: &RegionProperties, outer: &[Point<u32>]) -> ShapeMetrics {
+    let area = properties.area as f64;
+    let middles = crack_middles(outer);
+    let (smoothed_area, smoothed_perimeter) = (polygon_area(&middles), polygon_perimeter(&middles));
+    let hull = to_floats(&convex_hull(outer));
+    let hull_area = polygon_area(&hull);
+    ShapeMetrics {
+        polsby_popper: 4.0 * PI * smoothed_area / (smoothed_perimeter * smoothed_perimeter),
+        hull_area,
+        solidity: area / hull_area,
+        convexity: polygon_perimeter(&hull) / polygon_perimeter(&to_floats(outer)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_labelling::properties::region_properties;
+    use crate::region_labelling::{find_contour_hierarchy_in_luma, Tracing};
+    use image::{ImageBuffer, Luma};
+    use imageproc::definitions::Image;
+    use imageproc::region_labelling::Connectivity;
+
+    #[test]
+    fn test_convex_hull_drops_concave_and_collinear_points() {
+        let points = vec![
+            Point::new(0, 0),
+            Point::new(1, 0),
+            Point::new(2, 0),
+            Point::new(2, 1),
+            Point::new(1, 1),
+            Point::new(1, 2),
+            Point::new(0, 2),
+            Point::new(0, 1),
+        ];
+
+        assert_eq!(
+            vec![
+                Point::new(0, 0),
+                Point::new(2, 0),
+                Point::new(2, 1),
+                Point::new(1, 2),
+                Point::new(0, 2),
+            ],
+            convex_hull(&points)
+        );
+    }
+
+    #[test]
+    fn test_shape_metrics_of_square_and_l_shape() {
+        let image = gray_image!(type: u32,
+            1, 1, 0, 2, 2;
+            1, 1, 0, 2, 0);
+
+        let background_color = Luma([0u32; 1]);
+
+        let properties = region_properties(background_color, &image);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let square = shape_metrics(&properties[0], &hierarchy.contours[0]);
+        assert_eq!(4.0, square.hull_area);
+        assert_eq!(1.0, square.solidity);
+        assert_eq!(1.0, square.convexity);
+        let (area, perimeter) = (3.5, 4.0 + 2.0 * 2.0f64.sqrt());
+        assert!((square.polsby_popper - 4.0 * PI * area / (perimeter * perimeter)).abs() < 1e-9);
+
+        let l_shape = shape_metrics(&properties[1], &hierarchy.contours[1]);
+        assert_eq!(3.5, l_shape.hull_area);
+        assert_eq!(3.0 / 3.5, l_shape.solidity);
+        assert!((l_shape.convexity - (6.0 + 2.0f64.sqrt()) / 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shape_metrics_polsby_popper_ranks_disc_above_square() {
+        let image: Image<Luma<u32>> = ImageBuffer::from_fn(33, 17, |x, y| {
+            let (dx, dy) = (x as f64 - 7.5, y as f64 - 7.5);
+            if x < 16 && y < 16 && dx * dx + dy * dy <= 7.0 * 7.0 {
+                Luma([1])
+            } else if (18..32).contains(&x) && (1..15).contains(&y) {
+                Luma([2])
+            } else {
+                Luma([0])
+            }
+        });
+
+        let background_color = Luma([0u32; 1]);
+
+        let properties = region_properties(background_color, &image);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let disc = shape_metrics(&properties[0], &hierarchy.contours[0]);
+        let square = shape_metrics(&properties[1], &hierarchy.contours[1]);
+        assert!(disc.polsby_popper > square.polsby_popper);
+        assert!(
+            disc.polsby_popper > 0.9 && disc.polsby_popper <= 1.0,
+            "{:?}",
+            disc
+        );
+        assert!(
+            (square.polsby_popper - PI / 4.0).abs() < 0.05,
+            "{:?}",
+            square
+        );
+    }
+
+    #[test]
+    fn test_shape_metrics_convexity_ignores_holes() {
+        let image = gray_image!(type: u32,
+            1, 1, 1;
+            1, 0, 1;
+            1, 1, 1);
+
+        let background_color = Luma([0u32; 1]);
+
+        let properties = region_properties(background_color, &image);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let ring = shape_metrics(&properties[0], &hierarchy.contours[0]);
+        assert_eq!(16, properties[0].perimeter);
+        assert_eq!(1.0, ring.convexity);
+        assert_eq!(8.0 / 9.0, ring.solidity);
+    }
+}
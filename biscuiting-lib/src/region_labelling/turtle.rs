@@ -1,5 +1,5 @@
 #[derive(PartialEq, Debug)]
-enum Direction {
+pub enum Direction {
     North,
     West,
     South,
@@ -23,9 +23,13 @@ impl Turtle {
     }
 
     pub fn reset(&mut self, x: u32, y: u32) {
+        self.reset_heading(x, y, Direction::East);
+    }
+
+    pub fn reset_heading(&mut self, x: u32, y: u32, direction: Direction) {
         self.x = x as i32;
         self.y = y as i32;
-        self.direction = Direction::East;
+        self.direction = direction;
     }
 
     pub fn left(&self) -> Turtle {
@@ -0,0 +1,402 @@
+use crate::region_labelling::Point;
+use std::collections::HashMap;
+
+/// An arc between nodes, in canonical direction, with the points that its
+/// simplification keeps.
+struct SharedArc {
+    points: Vec<(f32, f32)>,
+    keep: Vec<bool>,
+}
+
+impl SharedArc {
+    fn new(steps: Vec<(u32, u32)>, tolerance: f32) -> SharedArc {
+        let points: Vec<(f32, f32)> = steps.iter().map(|(x, y)| (*x as f32, *y as f32)).collect();
+        let mut keep = vec![false; points.len()];
+        mark_kept(&points, 0, points.len() - 1, tolerance, &mut keep);
+        SharedArc { points, keep }
+    }
+
+    fn num_kept(&self) -> usize {
+        self.keep.iter().filter(|keep| **keep).count()
+    }
+
+    /// The dropped point furthest from the outline of the kept points, and how
+    /// far it is.
+    fn furthest_dropped(&self) -> Option<(usize, f32)> {
+        let mut previous = 0;
+        let mut furthest: Option<(usize, f32)> = None;
+        for index in 1..self.points.len() {
+            if self.keep[index] {
+                previous = index;
+                continue;
+            }
+            let next = (index + 1..self.points.len())
+                .find(|next| self.keep[*next])
+                .unwrap();
+            let (start, end) = (self.points[previous], self.points[next]);
+            let offset = distance_to_segment(self.points[index], start, end);
+            if !matches!(furthest, Some((_, furthest)) if furthest >= offset) {
+                furthest = Some((index, offset));
+            }
+        }
+        furthest
+    }
+
+    fn kept_points(&self) -> Vec<(f32, f32)> {
+        self.points
+            .iter()
+            .zip(&self.keep)
+            .filter(|(_, keep)| **keep)
+            .map(|(point, _)| *point)
+            .collect()
+    }
+}
+
+/// Ramer–Douglas–Peucker simplification of a closed ring, given without its
+/// first point repeated at the end. Points are dropped while every dropped
+/// point stays within `tolerance` of the simplified outline.
+///
+/// The ring is split at its first point and the point furthest from it, and
+/// each half is simplified as an open polyline, so both split points are kept.
+pub fn simplify_ring(ring: &[(f32, f32)], tolerance: f32) -> Vec<(f32, f32)> {
+    if ring.len() < 3 || tolerance <= 0.0 {
+        return ring.to_vec();
+    }
+    let furthest = (1..ring.len())
+        .max_by(|a, b| {
+            distance(ring[0], ring[*a])
+                .partial_cmp(&distance(ring[0], ring[*b]))
+                .unwrap()
+        })
+        .unwrap();
+    if distance(ring[0], ring[furthest]) == 0.0 {
+        return vec![ring[0]];
+    }
+    let mut closed = ring.to_vec();
+    closed.push(ring[0]);
+    let mut keep = vec![false; closed.len()];
+    mark_kept(&closed, 0, furthest, tolerance, &mut keep);
+    mark_kept(&closed, furthest, ring.len(), tolerance, &mut keep);
+    ring.iter()
+        .zip(&keep)
+        .filter(|(_, keep)| **keep)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+/// Simplifies rings that follow pixel cracks so that neighbouring regions still
+/// meet exactly. Each ring is cut at the corners where `is_node` holds into arcs,
+/// each arc is simplified once however many rings it is met in, and the rings
+/// are rebuilt from the simplified arcs. Rings without any node are simplified
+/// whole, from a canonical starting point and direction, so a region and the
+/// hole it fills agree too. Any ring left with fewer than three points gets back
+/// the dropped points furthest from its outline, in every ring sharing them.
+pub fn simplify_shared_arcs<F>(
+    rings: &[Vec<Point<u32>>],
+    is_node: F,
+    tolerance: f32,
+) -> Vec<Vec<(f32, f32)>>
+where
+    F: Fn(Point<u32>) -> bool,
+{
+    let mut arcs: Vec<SharedArc> = Vec::new();
+    let mut arc_indexes: HashMap<Vec<(u32, u32)>, usize> = HashMap::new();
+    let mut find_arc = |steps: &[(u32, u32)]| {
+        let forwards = steps.to_vec();
+        let mut backwards = forwards.clone();
+        backwards.reverse();
+        let is_reversed = backwards < forwards;
+        let key = if is_reversed { backwards } else { forwards };
+        let index = *arc_indexes.entry(key).or_insert_with_key(|key| {
+            arcs.push(SharedArc::new(key.clone(), tolerance));
+            arcs.len() - 1
+        });
+        (index, is_reversed)
+    };
+    let ring_arcs: Vec<Vec<(usize, bool)>> = rings
+        .iter()
+        .map(|ring| {
+            let mut steps = unit_steps(ring);
+            let first_node = steps.iter().position(|(x, y)| is_node(Point::new(*x, *y)));
+            match first_node {
+                Some(first_node) => {
+                    steps.rotate_left(first_node);
+                    steps.push(steps[0]);
+                    let mut ring_arcs = Vec::new();
+                    let mut start = 0;
+                    for end in 1..steps.len() {
+                        let (x, y) = steps[end];
+                        if end == steps.len() - 1 || is_node(Point::new(x, y)) {
+                            ring_arcs.push(find_arc(&steps[start..=end]));
+                            start = end;
+                        }
+                    }
+                    ring_arcs
+                }
+                None => {
+                    let smallest = (0..steps.len()).min_by_key(|index| steps[*index]).unwrap();
+                    steps.rotate_left(smallest);
+                    steps.push(steps[0]);
+                    vec![find_arc(&steps)]
+                }
+            }
+        })
+        .collect();
+    let mut is_settled = false;
+    while !is_settled {
+        is_settled = true;
+        for ring in &ring_arcs {
+            let num_points: usize = ring.iter().map(|(arc, _)| arcs[*arc].num_kept() - 1).sum();
+            if num_points >= 3 {
+                continue;
+            }
+            let furthest = ring
+                .iter()
+                .filter_map(|(arc, _)| {
+                    arcs[*arc]
+                        .furthest_dropped()
+                        .map(|(index, offset)| (*arc, index, offset))
+                })
+                .max_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap());
+            if let Some((arc, index, _)) = furthest {
+                arcs[arc].keep[index] = true;
+                is_settled = false;
+            }
+        }
+    }
+    ring_arcs
+        .iter()
+        .map(|ring| {
+            let mut simplified = Vec::new();
+            for (arc, is_reversed) in ring {
+                let mut points = arcs[*arc].kept_points();
+                if *is_reversed {
+                    points.reverse();
+                }
+                simplified.extend_from_slice(&points[..points.len() - 1]);
+            }
+            simplified
+        })
+        .collect()
+}
+
+/// Every crack corner along a ring of axis-aligned segments, one pixel apart.
+fn unit_steps(ring: &[Point<u32>]) -> Vec<(u32, u32)> {
+    let mut steps = Vec::new();
+    for (index, start) in ring.iter().enumerate() {
+        let end = ring[(index + 1) % ring.len()];
+        let (mut x, mut y) = (start.x as i64, start.y as i64);
+        let (dx, dy) = ((end.x as i64 - x).signum(), (end.y as i64 - y).signum());
+        while (x, y) != (end.x as i64, end.y as i64) {
+            steps.push((x as u32, y as u32));
+            x += dx;
+            y += dy;
+        }
+    }
+    steps
+}
+
+/// Simplifies the open polyline `points[first..=last]`, marking the points to
+/// keep. Both ends are always kept.
+fn mark_kept(points: &[(f32, f32)], first: usize, last: usize, tolerance: f32, keep: &mut [bool]) {
+    keep[first] = true;
+    keep[last] = true;
+    let mut pending = vec![(first, last)];
+    while let Some((first, last)) = pending.pop() {
+        let furthest = (first + 1..last)
+            .map(|index| {
+                let offset = distance_to_segment(points[index], points[first], points[last]);
+                (index, offset)
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+        if let Some((index, offset)) = furthest {
+            if offset > tolerance {
+                keep[index] = true;
+                pending.push((first, index));
+                pending.push((index, last));
+            }
+        }
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+fn distance_to_segment(point: (f32, f32), start: (f32, f32), end: (f32, f32)) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        return distance(point, start);
+    }
+    let t =
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0);
+    distance(point, (start.0 + t * dx, start.1 + t * dy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region_labelling::{
+        find_contour_hierarchy_in_luma, is_arc_end, is_junction, Tracing,
+    };
+    use image::Luma;
+    use imageproc::region_labelling::Connectivity;
+
+    #[test]
+    fn test_simplify_ring_drops_points_along_straight_edges() {
+        let ring = vec![
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (2.0, 2.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 1.0),
+        ];
+
+        assert_eq!(
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)],
+            simplify_ring(&ring, 0.1)
+        );
+    }
+
+    #[test]
+    fn test_simplify_ring_keeps_bumps_larger_than_tolerance() {
+        let ring = vec![
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, -0.5),
+            (3.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (0.0, 4.0),
+        ];
+
+        assert_eq!(
+            vec![(0.0, 0.0), (2.0, -0.5), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)],
+            simplify_ring(&ring, 0.25)
+        );
+        assert_eq!(
+            vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)],
+            simplify_ring(&ring, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_simplify_shared_arcs_keeps_neighbours_meeting() {
+        let image = gray_image!(type: u32,
+            1, 1, 2, 2, 2, 2;
+            1, 1, 1, 2, 2, 2;
+            1, 1, 1, 1, 2, 2;
+            1, 1, 1, 1, 1, 2);
+
+        let background_color = Luma([0u32; 1]);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let rings = simplify_shared_arcs(
+            &hierarchy.contours,
+            |point| is_junction(background_color, &image, point.x, point.y),
+            0.9,
+        );
+
+        assert_eq!(
+            vec![
+                vec![(2.0, 0.0), (5.0, 4.0), (0.0, 4.0), (0.0, 0.0)],
+                vec![(2.0, 0.0), (6.0, 0.0), (6.0, 4.0), (5.0, 4.0)],
+            ],
+            rings
+        );
+    }
+
+    #[test]
+    fn test_simplify_shared_arcs_keeps_image_corners() {
+        let image = gray_image!(type: u32,
+            1, 1, 1, 1, 1, 1, 1, 1, 1, 2;
+            1, 1, 1, 1, 1, 1, 1, 1, 1, 2;
+            1, 1, 1, 1, 1, 1, 1, 1, 1, 2);
+
+        let background_color = Luma([0u32; 1]);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let rings = simplify_shared_arcs(
+            &hierarchy.contours,
+            |point| is_arc_end(background_color, &image, point.x, point.y),
+            10.0,
+        );
+
+        assert_eq!(
+            vec![
+                vec![(0.0, 0.0), (9.0, 0.0), (9.0, 3.0), (0.0, 3.0)],
+                vec![(9.0, 0.0), (10.0, 0.0), (10.0, 3.0), (9.0, 3.0)],
+            ],
+            rings
+        );
+    }
+
+    #[test]
+    fn test_simplify_shared_arcs_keeps_three_points_per_ring() {
+        let image = gray_image!(type: u32,
+            1, 1, 1;
+            1, 2, 1;
+            1, 1, 1);
+
+        let background_color = Luma([0u32; 1]);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let rings = simplify_shared_arcs(
+            &hierarchy.contours,
+            |point| is_arc_end(background_color, &image, point.x, point.y),
+            10.0,
+        );
+
+        assert_eq!(4, rings[0].len());
+        assert_eq!(3, rings[1].len());
+        assert_eq!(3, rings[2].len());
+    }
+
+    #[test]
+    fn test_simplify_shared_arcs_matches_island_to_its_hole() {
+        let image = gray_image!(type: u32,
+            1, 1, 1, 1, 1;
+            1, 2, 2, 2, 1;
+            1, 2, 2, 1, 1;
+            1, 1, 1, 1, 1);
+
+        let background_color = Luma([0u32; 1]);
+        let hierarchy = find_contour_hierarchy_in_luma(
+            background_color,
+            &image,
+            Tracing::Cracks,
+            Connectivity::Four,
+        );
+
+        let rings = simplify_shared_arcs(
+            &hierarchy.contours,
+            |point| is_junction(background_color, &image, point.x, point.y),
+            0.8,
+        );
+
+        let mut hole = rings[1].clone();
+        let mut island = rings[2].clone();
+        hole.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        island.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(hole, island);
+    }
+}